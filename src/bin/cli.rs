@@ -1,3 +1,4 @@
+use celerix_store::engine::{persistence, vault::SecretBytes, Persistence};
 use celerix_store::sdk;
 use clap::{Parser, Subcommand};
 use serde_json::Value;
@@ -17,15 +18,65 @@ enum Commands {
     Get { persona: String, app: String, key: String },
     Set { persona: String, app: String, key: String, value: String },
     Del { persona: String, app: String, key: String },
+    Incr { persona: String, app: String, key: String, #[arg(default_value_t = 1, allow_hyphen_values = true)] delta: i64 },
     ListPersonas,
     ListApps { persona: String },
     Dump { persona: String, app: String },
     Move { src_persona: String, dst_persona: String, app: String, key: String },
+    /// Show the recorded versions of a key, newest first
+    History { persona: String, app: String, key: String },
+    /// Restore a key to its value at a timestamp (milliseconds since the Unix epoch)
+    Revert { persona: String, app: String, key: String, timestamp: u64 },
+    /// List the soft-deleted keys of a persona
+    Trash { persona: String },
+    /// Move a key from the trash back into its app
+    Restore { persona: String, app: String, key: String },
+    /// Permanently remove a key from the trash
+    Purge { persona: String, app: String, key: String },
+    /// Index a JSON pointer path (e.g. /email) of an app's values
+    CreateIndex { app: String, path: String },
+    DropIndex { app: String, path: String },
+    ListIndexes { app: String },
+    /// Find the persona/key pairs whose indexed field equals a value
+    FindIndex { app: String, path: String, value: String },
+    /// Convert a plain data directory to encrypted-at-rest persona files
+    Encrypt { #[arg(long)] key_file: Option<String> },
+    /// Convert an encrypted data directory back to plain JSON persona files
+    Decrypt { #[arg(long)] key_file: Option<String> },
+}
+
+fn resolve_key(key_file: Option<String>) -> anyhow::Result<SecretBytes> {
+    let key = match key_file {
+        Some(path) => Some(persistence::read_key_file(path)?),
+        None => persistence::key_from_env()?,
+    };
+    key.ok_or_else(|| anyhow::anyhow!("no data key: pass --key-file or set CELERIX_DATA_KEY"))
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+
+    match cli.command.clone() {
+        Commands::Encrypt { key_file } => {
+            let key = resolve_key(key_file)?;
+            let plain = Persistence::new(&cli.data_dir)?;
+            let encrypted = Persistence::with_encryption(&cli.data_dir, key.expose())?;
+            let count = plain.migrate_to(&encrypted)?;
+            println!("Encrypted {} personas.", count);
+            return Ok(());
+        }
+        Commands::Decrypt { key_file } => {
+            let key = resolve_key(key_file)?;
+            let encrypted = Persistence::with_encryption(&cli.data_dir, key.expose())?;
+            let plain = Persistence::new(&cli.data_dir)?;
+            let count = encrypted.migrate_to(&plain)?;
+            println!("Decrypted {} personas.", count);
+            return Ok(());
+        }
+        _ => {}
+    }
+
     let store = sdk::new(&cli.data_dir).await?;
 
     match cli.command {
@@ -42,6 +93,10 @@ async fn main() -> anyhow::Result<()> {
             store.delete(&persona, &app, &key).await?;
             println!("OK");
         }
+        Commands::Incr { persona, app, key, delta } => {
+            let val = store.incr(&persona, &app, &key, delta).await?;
+            println!("{}", val);
+        }
         Commands::ListPersonas => {
             let list = store.get_personas().await?;
             println!("{}", serde_json::to_string_pretty(&list)?);
@@ -58,6 +113,44 @@ async fn main() -> anyhow::Result<()> {
             store.move_key(&src_persona, &dst_persona, &app, &key).await?;
             println!("OK");
         }
+        Commands::History { persona, app, key } => {
+            let entries = store.history(&persona, &app, &key).await?;
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+        }
+        Commands::Revert { persona, app, key, timestamp } => {
+            store.revert(&persona, &app, &key, timestamp).await?;
+            println!("OK");
+        }
+        Commands::Trash { persona } => {
+            let entries = store.list_trash(&persona).await?;
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+        }
+        Commands::Restore { persona, app, key } => {
+            store.restore(&persona, &app, &key).await?;
+            println!("OK");
+        }
+        Commands::Purge { persona, app, key } => {
+            store.purge(&persona, &app, &key).await?;
+            println!("OK");
+        }
+        Commands::CreateIndex { app, path } => {
+            store.create_index(&app, &path).await?;
+            println!("OK");
+        }
+        Commands::DropIndex { app, path } => {
+            store.drop_index(&app, &path).await?;
+            println!("OK");
+        }
+        Commands::ListIndexes { app } => {
+            let list = store.list_indexes(&app).await?;
+            println!("{}", serde_json::to_string_pretty(&list)?);
+        }
+        Commands::FindIndex { app, path, value } => {
+            let val: Value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+            let matches = store.find_by_index(&app, &path, &val).await?;
+            println!("{}", serde_json::to_string_pretty(&matches)?);
+        }
+        Commands::Encrypt { .. } | Commands::Decrypt { .. } => unreachable!(),
     }
 
     Ok(())
@@ -1,6 +1,6 @@
 use std::sync::Arc;
-use celerix_store::{engine::{MemStore, Persistence}, AppEnumeration};
-use celerix_store::server::Router;
+use celerix_store::{engine::{history::HistoryRetention, hooks::HookScope, persistence, queue, trash, MemStore, Persistence}, AppEnumeration};
+use celerix_store::server::{hooks, Router};
 use clap::Parser;
 use std::env;
 use tokio::signal;
@@ -13,6 +13,46 @@ struct Args {
 
     #[arg(short, long)]
     port: Option<String>,
+
+    /// File containing the 32-byte key used to encrypt persona files at rest
+    #[arg(long)]
+    key_file: Option<String>,
+
+    /// Deliveries before a queue message is moved to the dead-letter list
+    #[arg(long)]
+    queue_max_attempts: Option<u32>,
+
+    /// Past versions kept per key (default 0, history off)
+    #[arg(long)]
+    history_versions: Option<usize>,
+
+    /// Keep the versions of the last N seconds instead of a fixed number
+    #[arg(long)]
+    history_window_secs: Option<u64>,
+
+    /// Move deleted keys to a trash and keep them there for N seconds
+    #[arg(long)]
+    trash_retention_secs: Option<u64>,
+
+    /// POST every change as JSON to this http:// URL (repeatable)
+    #[arg(long)]
+    webhook: Vec<String>,
+
+    /// Run this shell command for every change, with the change as JSON on stdin (repeatable)
+    #[arg(long)]
+    hook_command: Vec<String>,
+
+    /// Only run hooks for changes in this persona
+    #[arg(long)]
+    hook_persona: Option<String>,
+
+    /// Only run hooks for changes in this app
+    #[arg(long)]
+    hook_app: Option<String>,
+
+    /// Only run hooks for keys starting with this prefix
+    #[arg(long)]
+    hook_key_prefix: Option<String>,
 }
 
 #[tokio::main]
@@ -28,14 +68,63 @@ async fn main() -> anyhow::Result<()> {
         .or_else(|| env::var("CELERIX_PORT").ok())
         .unwrap_or_else(|| "7001".to_string());
 
-    let persistence = Arc::new(Persistence::new(&data_dir)?);
+    let key = match args.key_file {
+        Some(path) => Some(persistence::read_key_file(path)?),
+        None => persistence::key_from_env()?,
+    };
+
+    let persistence = match &key {
+        Some(key) => Persistence::with_encryption(&data_dir, key.expose())?,
+        None => Persistence::new(&data_dir)?,
+    };
+    let persistence = Arc::new(persistence);
     let initial_data = persistence.load_all()?;
-    let store = Arc::new(MemStore::new(initial_data, Some(persistence)));
+    let queue_max_attempts = args.queue_max_attempts
+        .or_else(|| env::var("CELERIX_QUEUE_MAX_ATTEMPTS").ok().and_then(|v| v.parse().ok()))
+        .unwrap_or(queue::DEFAULT_MAX_ATTEMPTS);
+
+    let history = HistoryRetention::resolve(args.history_window_secs, args.history_versions);
+
+    let trash_retention = trash::retention(args.trash_retention_secs);
+
+    let mut store = MemStore::new(initial_data, Some(persistence))
+        .with_queue_max_attempts(queue_max_attempts)
+        .with_history(history);
+    if let Some(retention) = trash_retention {
+        store = store.with_trash(retention);
+    }
+    let store = Arc::new(store);
+
+    let mut webhooks = args.webhook;
+    webhooks.extend(env::var("CELERIX_WEBHOOK_URL").ok());
+    let mut commands = args.hook_command;
+    commands.extend(env::var("CELERIX_HOOK_COMMAND").ok());
+    let scope = HookScope {
+        persona_id: args.hook_persona.or_else(|| env::var("CELERIX_HOOK_PERSONA").ok()),
+        app_id: args.hook_app.or_else(|| env::var("CELERIX_HOOK_APP").ok()),
+        key_prefix: args.hook_key_prefix.or_else(|| env::var("CELERIX_HOOK_KEY_PREFIX").ok()),
+    };
+    let mut configured = Vec::new();
+    for url in &webhooks {
+        configured.push(hooks::webhook(url)?);
+    }
+    configured.extend(commands.iter().map(|cmd| hooks::command(cmd)));
+    for hook in &configured {
+        let (on_set, on_delete) = (hook.clone(), hook.clone());
+        store.on_after_set(scope.clone(), move |event| on_set(event));
+        store.on_after_delete(scope.clone(), move |event| on_delete(event));
+    }
 
     let router = Router::new(store.clone());
     
     println!("Starting Celerix Store Daemon...");
     println!("Engine started. Loaded {} personas.", store.get_personas().await?.len());
+    if key.is_some() {
+        println!("Encryption at rest enabled.");
+    }
+    if !configured.is_empty() {
+        println!("Running {} change hooks.", configured.len());
+    }
     println!("Celerix Engine listening on :{} (TCP)", port);
 
     tokio::select! {
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+use crate::{SYSTEM_PERSONA, Derivation, DerivedKey, KeyRef};
+use crate::engine::layers;
+use crate::engine::memstore::StoreData;
+
+/// App within [`SYSTEM_PERSONA`] holding the derived key definitions, keyed by [`definition_key`].
+pub const DERIVED_APP: &str = "_derived";
+
+/// How many derived keys may feed into each other before recomputation stops, guarding against cycles.
+pub const MAX_DEPTH: usize = 8;
+
+/// Returns the key under which the definition of a derived key is stored.
+pub fn definition_key(target: &KeyRef) -> String {
+    format!("{}/{}/{}", target.persona_id, target.app_id, target.key)
+}
+
+/// Reads all derived key definitions stored in [`SYSTEM_PERSONA`].
+pub fn definitions(data: &StoreData) -> Vec<DerivedKey> {
+    let mut defs: Vec<DerivedKey> = data.get(SYSTEM_PERSONA)
+        .and_then(|p| p.get(DERIVED_APP))
+        .map(|defs| defs.values().filter_map(|v| serde_json::from_value(v.clone()).ok()).collect())
+        .unwrap_or_default();
+    defs.sort_by(|a, b| a.target.cmp(&b.target));
+    defs
+}
+
+/// Computes the current value of a derived key, or `None` if it should not exist.
+pub fn compute(data: &StoreData, derivation: &Derivation) -> Option<serde_json::Value> {
+    match derivation {
+        Derivation::Count { persona_id, app_id } => {
+            let count = data.get(persona_id).and_then(|p| p.get(app_id)).map(|a| a.len()).unwrap_or(0);
+            Some(serde_json::json!(count))
+        }
+        Derivation::Merge { sources } => {
+            // Layers are resolved most specific first, which is the last source
+            let layers: Vec<(String, &serde_json::Value)> = sources.iter().rev()
+                .filter_map(|s| {
+                    let value = data.get(&s.persona_id)?.get(&s.app_id)?.get(&s.key)?;
+                    Some((s.persona_id.clone(), value))
+                })
+                .collect();
+            layers::resolve(&layers, true).ok().map(|effective| effective.value)
+        }
+    }
+}
+
+/// The derived key definitions, grouped by the persona and app of their inputs.
+#[derive(Debug, Default)]
+pub struct DerivedSet {
+    by_input: HashMap<(String, String), Vec<DerivedKey>>,
+    targets: HashSet<KeyRef>,
+}
+
+impl DerivedSet {
+    /// Groups the definitions stored in `data` by input.
+    pub fn build(data: &StoreData) -> Self {
+        let mut set = Self::default();
+        for def in definitions(data) {
+            set.targets.insert(def.target.clone());
+            let inputs: Vec<(String, String)> = match &def.derivation {
+                Derivation::Count { persona_id, app_id } => vec![(persona_id.clone(), app_id.clone())],
+                Derivation::Merge { sources } => sources.iter().map(|s| (s.persona_id.clone(), s.app_id.clone())).collect(),
+            };
+            for input in inputs {
+                let defs = set.by_input.entry(input).or_default();
+                if !defs.contains(&def) {
+                    defs.push(def.clone());
+                }
+            }
+        }
+        set
+    }
+
+    /// Returns whether a key holds the value of a derived key.
+    pub fn is_target(&self, persona_id: &str, app_id: &str, key: &str) -> bool {
+        !self.targets.is_empty() && self.targets.contains(&KeyRef::new(persona_id, app_id, key))
+    }
+
+    /// Returns the derived keys that depend on a key.
+    pub fn affected(&self, persona_id: &str, app_id: &str, key: &str) -> Vec<DerivedKey> {
+        let Some(defs) = self.by_input.get(&(persona_id.to_string(), app_id.to_string())) else { return Vec::new() };
+        defs.iter()
+            .filter(|def| match &def.derivation {
+                Derivation::Count { .. } => true,
+                Derivation::Merge { sources } => sources.iter().any(|s| s.persona_id == persona_id && s.app_id == app_id && s.key == key),
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compute_and_affected() {
+        let mut data = StoreData::new();
+        let config = data.entry("p1".to_string()).or_default().entry("config".to_string()).or_default();
+        config.insert("base".to_string(), json!({"theme": "light", "limits": {"max": 5}}));
+        config.insert("override".to_string(), json!({"limits": {"max": 10}}));
+
+        let merge = Derivation::Merge { sources: vec![KeyRef::new("p1", "config", "base"), KeyRef::new("p1", "config", "override"), KeyRef::new("p1", "config", "missing")] };
+        assert_eq!(compute(&data, &merge), Some(json!({"theme": "light", "limits": {"max": 10}})));
+        assert_eq!(compute(&data, &Derivation::Merge { sources: vec![KeyRef::new("p2", "config", "base")] }), None);
+        let count = Derivation::Count { persona_id: "p1".to_string(), app_id: "config".to_string() };
+        assert_eq!(compute(&data, &count), Some(json!(2)));
+
+        let def = DerivedKey { target: KeyRef::new("p1", "stats", "effective"), derivation: merge };
+        data.entry(SYSTEM_PERSONA.to_string()).or_default()
+            .entry(DERIVED_APP.to_string()).or_default()
+            .insert(definition_key(&def.target), serde_json::to_value(&def).unwrap());
+        let set = DerivedSet::build(&data);
+        assert_eq!(set.affected("p1", "config", "override"), vec![def]);
+        assert!(set.affected("p1", "config", "unrelated").is_empty());
+        assert!(set.affected("p2", "config", "base").is_empty());
+        assert!(set.is_target("p1", "stats", "effective"));
+        assert!(!set.is_target("p1", "config", "override"));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::{HashMap, HashSet};
+use crate::{SYSTEM_PERSONA, SearchHit};
+use crate::engine::memstore::StoreData;
+
+/// App within [`SYSTEM_PERSONA`] whose keys are the app IDs with full-text search enabled.
+pub const FULLTEXT_APP: &str = "_fulltext";
+
+/// Returns the app IDs with full-text search enabled.
+pub fn enabled_apps(data: &StoreData) -> HashSet<String> {
+    data.get(SYSTEM_PERSONA)
+        .and_then(|p| p.get(FULLTEXT_APP))
+        .map(|apps| apps.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Splits text into lowercase alphanumeric terms.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Counts the terms of every string within a JSON value.
+fn term_counts(value: &serde_json::Value) -> HashMap<String, u32> {
+    fn collect(value: &serde_json::Value, counts: &mut HashMap<String, u32>) {
+        match value {
+            serde_json::Value::String(s) => {
+                for term in tokenize(s) {
+                    *counts.entry(term).or_default() += 1;
+                }
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, counts)),
+            serde_json::Value::Object(map) => map.values().for_each(|v| collect(v, counts)),
+            _ => {}
+        }
+    }
+    let mut counts = HashMap::new();
+    collect(value, &mut counts);
+    counts
+}
+
+/// The inverted index of one app within one persona.
+#[derive(Debug, Default)]
+struct Postings {
+    /// Term -> key -> number of occurrences.
+    terms: HashMap<String, HashMap<String, u32>>,
+    /// Key -> number of terms, for length normalization.
+    lengths: HashMap<String, u32>,
+}
+
+impl Postings {
+    fn remove(&mut self, key: &str, value: &serde_json::Value) {
+        for term in term_counts(value).into_keys() {
+            if let Some(keys) = self.terms.get_mut(&term) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.terms.remove(&term);
+                }
+            }
+        }
+        self.lengths.remove(key);
+    }
+
+    fn insert(&mut self, key: &str, value: &serde_json::Value) {
+        let counts = term_counts(value);
+        if counts.is_empty() {
+            return;
+        }
+        self.lengths.insert(key.to_string(), counts.values().sum());
+        for (term, count) in counts {
+            self.terms.entry(term).or_default().insert(key.to_string(), count);
+        }
+    }
+}
+
+/// In-memory full-text indexes of the apps that enabled search.
+///
+/// Nothing but the set of enabled apps is persisted; the postings are rebuilt on startup.
+#[derive(Debug, Default)]
+pub struct TextIndex {
+    /// App ID -> persona ID -> postings.
+    apps: HashMap<String, HashMap<String, Postings>>,
+}
+
+impl TextIndex {
+    /// Indexes the string values of all enabled apps in `data`.
+    pub fn build(data: &StoreData) -> Self {
+        let mut index = Self::default();
+        for app_id in enabled_apps(data) {
+            index.apps.insert(app_id, HashMap::new());
+        }
+        for (persona_id, apps) in data {
+            for (app_id, app_data) in apps {
+                if !index.apps.contains_key(app_id) {
+                    continue;
+                }
+                for (key, value) in app_data {
+                    index.update(persona_id, app_id, key, None, Some(value));
+                }
+            }
+        }
+        index
+    }
+
+    /// Replaces a key's postings for its `old` value with those of its `new` value.
+    pub fn update(&mut self, persona_id: &str, app_id: &str, key: &str, old: Option<&serde_json::Value>, new: Option<&serde_json::Value>) {
+        let Some(personas) = self.apps.get_mut(app_id) else { return };
+        let postings = personas.entry(persona_id.to_string()).or_default();
+        if let Some(old) = old {
+            postings.remove(key, old);
+        }
+        if let Some(new) = new {
+            postings.insert(key, new);
+        }
+    }
+
+    /// Ranks the keys of a persona's app against `terms` with TF-IDF, best first.
+    ///
+    /// A key matches if it contains any of the terms. Returns `None` if search is not enabled for the app.
+    pub fn search(&self, persona_id: &str, app_id: &str, terms: &str) -> Option<Vec<SearchHit>> {
+        let personas = self.apps.get(app_id)?;
+        let Some(postings) = personas.get(persona_id) else { return Some(Vec::new()) };
+
+        let total = postings.lengths.len() as f64;
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        let unique: HashSet<String> = tokenize(terms).into_iter().collect();
+        for term in &unique {
+            let Some(keys) = postings.terms.get(term) else { continue };
+            let idf = (1.0 + total / keys.len() as f64).ln();
+            for (key, count) in keys {
+                let length = postings.lengths.get(key).copied().unwrap_or(1).max(1) as f64;
+                *scores.entry(key.as_str()).or_default() += (*count as f64 / length) * idf;
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = scores.into_iter()
+            .map(|(key, score)| SearchHit { key: key.to_string(), score })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.key.cmp(&b.key)));
+        Some(hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ranking_and_updates() {
+        let mut data = StoreData::new();
+        data.entry(SYSTEM_PERSONA.to_string()).or_default()
+            .entry(FULLTEXT_APP.to_string()).or_default()
+            .insert("notes".to_string(), json!(true));
+        let notes = data.entry("p1".to_string()).or_default().entry("notes".to_string()).or_default();
+        notes.insert("n1".to_string(), json!({"title": "Rust tips", "body": "Borrowing in Rust, explained"}));
+        notes.insert("n2".to_string(), json!({"title": "Groceries", "tags": ["milk", "rust remover"]}));
+        notes.insert("n3".to_string(), json!({"title": "Holiday plans"}));
+
+        let mut index = TextIndex::build(&data);
+        let keys = |hits: Vec<SearchHit>| hits.into_iter().map(|h| h.key).collect::<Vec<_>>();
+        assert_eq!(keys(index.search("p1", "notes", "RUST").unwrap()), vec!["n1", "n2"]);
+        assert!(index.search("p1", "other", "rust").is_none());
+        assert!(index.search("p2", "notes", "rust").unwrap().is_empty());
+
+        index.update("p1", "notes", "n3", Some(&json!({"title": "Holiday plans"})), None);
+        assert!(index.search("p1", "notes", "holiday").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+use crate::HistoryEntry;
+
+/// Environment variable holding the number of past versions kept per key.
+pub const VERSIONS_ENV: &str = "CELERIX_HISTORY_VERSIONS";
+/// Environment variable holding a retention window in seconds, which takes precedence
+/// over [`VERSIONS_ENV`].
+pub const WINDOW_SECS_ENV: &str = "CELERIX_HISTORY_WINDOW_SECS";
+
+/// Suffix of the sidecar app that holds the past values of an app's keys.
+pub const HISTORY_SUFFIX: &str = "::history";
+
+/// Returns the ID of the sidecar app holding the history of `app_id`.
+pub fn history_app(app_id: &str) -> String {
+    format!("{}{}", app_id, HISTORY_SUFFIX)
+}
+
+/// How much history is kept for each key.
+/// 
+/// History is off by default, since every tracked write then grows the persona file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryRetention {
+    /// Keep the last N versions. `Versions(0)` disables history.
+    Versions(usize),
+    /// Keep the versions written within this window, plus always the latest one.
+    Window(Duration),
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        Self::Versions(0)
+    }
+}
+
+impl HistoryRetention {
+    /// Returns whether any history is kept at all.
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::Versions(0)
+    }
+
+    /// Builds the retention from explicit settings, falling back to [`WINDOW_SECS_ENV`]
+    /// and [`VERSIONS_ENV`] for those not given. A window wins over a version count.
+    pub fn resolve(window_secs: Option<u64>, versions: Option<usize>) -> Self {
+        let window_secs = window_secs.or_else(|| env_var(WINDOW_SECS_ENV));
+        let versions = versions.or_else(|| env_var(VERSIONS_ENV));
+        match (window_secs, versions) {
+            (Some(secs), _) => Self::Window(Duration::from_secs(secs)),
+            (None, Some(versions)) => Self::Versions(versions),
+            (None, None) => Self::default(),
+        }
+    }
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+/// Parses a stored history log, oldest entry first.
+pub fn entries(log: Option<&serde_json::Value>) -> Vec<HistoryEntry> {
+    log.and_then(|v| serde_json::from_value(v.clone()).ok()).unwrap_or_default()
+}
+
+/// Appends `entry` to a stored log and applies `retention`.
+pub fn record(log: Option<&serde_json::Value>, entry: HistoryEntry, retention: HistoryRetention) -> serde_json::Value {
+    let mut list = entries(log);
+    match retention {
+        HistoryRetention::Versions(n) => {
+            let excess = (list.len() + 1).saturating_sub(n.max(1));
+            list.drain(..excess.min(list.len()));
+        }
+        HistoryRetention::Window(window) => {
+            let cutoff = entry.at.saturating_sub(window.as_millis() as u64);
+            list.retain(|e| e.at >= cutoff);
+        }
+    }
+    list.push(entry);
+    serde_json::to_value(list).unwrap_or_default()
+}
+
+/// Returns the latest entry written at or before `timestamp`, which may be a deletion.
+pub fn state_at(log: Option<&serde_json::Value>, timestamp: u64) -> Option<HistoryEntry> {
+    entries(log).into_iter().rev().find(|e| e.at <= timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write(at: u64, value: serde_json::Value) -> HistoryEntry {
+        HistoryEntry { at, value, deleted: false }
+    }
+
+    #[test]
+    fn test_retention_and_lookup() {
+        let mut log = None;
+        for i in 1..=4 {
+            log = Some(record(log.as_ref(), write(i * 100, json!(i)), HistoryRetention::Versions(3)));
+        }
+        let kept: Vec<u64> = entries(log.as_ref()).iter().map(|e| e.at).collect();
+        assert_eq!(kept, vec![200, 300, 400]);
+        assert_eq!(state_at(log.as_ref(), 350).unwrap().value, json!(3));
+        assert!(state_at(log.as_ref(), 150).is_none());
+
+        let window = HistoryRetention::Window(Duration::from_millis(150));
+        let log = record(log.as_ref(), write(500, json!(5)), window);
+        let kept: Vec<u64> = entries(Some(&log)).iter().map(|e| e.at).collect();
+        assert_eq!(kept, vec![400, 500]);
+
+        // Versions that left the window are dropped on the next write
+        let log = record(Some(&log), write(9000, json!(9)), window);
+        assert_eq!(entries(Some(&log)), vec![write(9000, json!(9))]);
+
+        // A deletion is a version too, and shadows older ones
+        let tombstone = HistoryEntry { at: 9100, value: serde_json::Value::Null, deleted: true };
+        let log = record(Some(&log), tombstone.clone(), window);
+        assert_eq!(state_at(Some(&log), 9200), Some(tombstone));
+        assert_eq!(state_at(Some(&log), 9050).unwrap().value, json!(9));
+    }
+}
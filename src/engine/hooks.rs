@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::Result;
+
+/// Transforms or rejects a value before it is stored: `(persona_id, app_id, key, value)`.
+pub type BeforeSetHook = Arc<dyn Fn(&str, &str, &str, serde_json::Value) -> Result<serde_json::Value> + Send + Sync>;
+/// Reacts to a change after it has been applied.
+pub type AfterHook = Arc<dyn Fn(&ChangeEvent) + Send + Sync>;
+
+/// Whether a change stored or removed a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// The key was written.
+    Set,
+    /// The key was removed.
+    Delete,
+}
+
+/// A change to a single key, as passed to after-set and after-delete hooks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// Whether the key was written or removed.
+    pub kind: ChangeKind,
+    /// The persona of the changed key.
+    pub persona_id: String,
+    /// The app of the changed key.
+    pub app_id: String,
+    /// The changed key.
+    pub key: String,
+    /// The new value, or `None` for deletes.
+    pub value: Option<serde_json::Value>,
+}
+
+/// Selects the keys a hook applies to. The default scope matches every key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookScope {
+    /// Only keys of this persona, if set.
+    pub persona_id: Option<String>,
+    /// Only keys of this app, if set.
+    pub app_id: Option<String>,
+    /// Only keys starting with this prefix, if set.
+    pub key_prefix: Option<String>,
+}
+
+impl HookScope {
+    /// Restricts the scope to one persona.
+    pub fn persona(mut self, persona_id: &str) -> Self {
+        self.persona_id = Some(persona_id.to_string());
+        self
+    }
+
+    /// Restricts the scope to one app.
+    pub fn app(mut self, app_id: &str) -> Self {
+        self.app_id = Some(app_id.to_string());
+        self
+    }
+
+    /// Restricts the scope to keys starting with `prefix`.
+    pub fn key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Returns whether a key falls within the scope.
+    pub fn matches(&self, persona_id: &str, app_id: &str, key: &str) -> bool {
+        self.persona_id.as_deref().is_none_or(|p| p == persona_id)
+            && self.app_id.as_deref().is_none_or(|a| a == app_id)
+            && self.key_prefix.as_deref().is_none_or(|prefix| key.starts_with(prefix))
+    }
+}
+
+/// The hooks registered on a store, run in registration order.
+#[derive(Default)]
+pub struct Hooks {
+    before_set: Vec<(HookScope, BeforeSetHook)>,
+    after_set: Vec<(HookScope, AfterHook)>,
+    after_delete: Vec<(HookScope, AfterHook)>,
+}
+
+impl Hooks {
+    /// Registers a hook run on every value about to be stored within `scope`.
+    pub fn add_before_set(&mut self, scope: HookScope, hook: BeforeSetHook) {
+        self.before_set.push((scope, hook));
+    }
+
+    /// Registers a hook run after a key within `scope` was written.
+    pub fn add_after_set(&mut self, scope: HookScope, hook: AfterHook) {
+        self.after_set.push((scope, hook));
+    }
+
+    /// Registers a hook run after a key within `scope` was removed.
+    pub fn add_after_delete(&mut self, scope: HookScope, hook: AfterHook) {
+        self.after_delete.push((scope, hook));
+    }
+
+    /// Passes a value through the matching before-set hooks, stopping at the first error.
+    pub fn before_set(&self, persona_id: &str, app_id: &str, key: &str, mut value: serde_json::Value) -> Result<serde_json::Value> {
+        for (scope, hook) in &self.before_set {
+            if scope.matches(persona_id, app_id, key) {
+                value = hook(persona_id, app_id, key, value)?;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Returns the after hooks that apply to a change of the given kind.
+    pub fn after(&self, kind: ChangeKind, persona_id: &str, app_id: &str, key: &str) -> Vec<AfterHook> {
+        let hooks = match kind {
+            ChangeKind::Set => &self.after_set,
+            ChangeKind::Delete => &self.after_delete,
+        };
+        hooks.iter()
+            .filter(|(scope, _)| scope.matches(persona_id, app_id, key))
+            .map(|(_, hook)| hook.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_scopes_and_chaining() {
+        let mut hooks = Hooks::default();
+        hooks.add_before_set(HookScope::default().app("flags"), Arc::new(|_, _, _, v| Ok(json!({"wrapped": v}))));
+        hooks.add_before_set(HookScope::default().persona("p1").key_prefix("beta_"), Arc::new(|_, _, key, v| Ok(json!([key, v]))));
+        hooks.add_after_delete(HookScope::default(), Arc::new(|_| {}));
+
+        assert_eq!(hooks.before_set("p1", "flags", "beta_x", json!(1)).unwrap(), json!(["beta_x", {"wrapped": 1}]));
+        assert_eq!(hooks.before_set("p2", "flags", "beta_x", json!(1)).unwrap(), json!({"wrapped": 1}));
+        assert_eq!(hooks.before_set("p2", "other", "k", json!(1)).unwrap(), json!(1));
+        assert!(hooks.after(ChangeKind::Set, "p1", "flags", "k").is_empty());
+        assert_eq!(hooks.after(ChangeKind::Delete, "p1", "flags", "k").len(), 1);
+    }
+}
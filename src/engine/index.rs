@@ -0,0 +1,115 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::SYSTEM_PERSONA;
+use crate::engine::memstore::StoreData;
+
+/// App within [`SYSTEM_PERSONA`] that maps each app ID to the JSON pointers indexed for it.
+pub const INDEXES_APP: &str = "_indexes";
+
+/// Entries of one index: the JSON-encoded field value mapped to the `(persona_id, key)` pairs holding it.
+type IndexEntries = HashMap<String, BTreeSet<(String, String)>>;
+
+/// In-memory secondary indexes over JSON fields.
+///
+/// Only the definitions are persisted; the entries are rebuilt from the data on startup.
+#[derive(Debug, Default)]
+pub struct IndexSet {
+    /// App ID -> indexed path -> entries.
+    apps: HashMap<String, HashMap<String, IndexEntries>>,
+}
+
+/// Reads the index definitions stored in [`SYSTEM_PERSONA`].
+pub fn definitions(data: &StoreData) -> HashMap<String, Vec<String>> {
+    data.get(SYSTEM_PERSONA)
+        .and_then(|p| p.get(INDEXES_APP))
+        .map(|defs| defs.iter()
+            .map(|(app_id, paths)| {
+                let paths = paths.as_array()
+                    .map(|list| list.iter().filter_map(|p| p.as_str().map(String::from)).collect())
+                    .unwrap_or_default();
+                (app_id.clone(), paths)
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Returns the key under which a field value is indexed.
+fn encode(value: &serde_json::Value) -> String {
+    value.to_string()
+}
+
+impl IndexSet {
+    /// Builds all indexes defined in `data` from its current contents.
+    pub fn build(data: &StoreData) -> Self {
+        let mut set = Self::default();
+        for (app_id, paths) in definitions(data) {
+            let app_indexes = set.apps.entry(app_id.clone()).or_default();
+            for path in paths {
+                app_indexes.entry(path).or_default();
+            }
+        }
+        for (persona_id, apps) in data {
+            for (app_id, app_data) in apps {
+                if !set.apps.contains_key(app_id) {
+                    continue;
+                }
+                for (key, value) in app_data {
+                    set.update(persona_id, app_id, key, None, Some(value));
+                }
+            }
+        }
+        set
+    }
+
+    /// Moves a key's index entries from its `old` value to its `new` value.
+    pub fn update(&mut self, persona_id: &str, app_id: &str, key: &str, old: Option<&serde_json::Value>, new: Option<&serde_json::Value>) {
+        let Some(app_indexes) = self.apps.get_mut(app_id) else { return };
+        let id = (persona_id.to_string(), key.to_string());
+        for (path, entries) in app_indexes.iter_mut() {
+            if let Some(field) = old.and_then(|v| v.pointer(path)) {
+                let encoded = encode(field);
+                if let Some(holders) = entries.get_mut(&encoded) {
+                    holders.remove(&id);
+                    if holders.is_empty() {
+                        entries.remove(&encoded);
+                    }
+                }
+            }
+            if let Some(field) = new.and_then(|v| v.pointer(path)) {
+                entries.entry(encode(field)).or_default().insert(id.clone());
+            }
+        }
+    }
+
+    /// Returns the sorted `(persona_id, key)` pairs whose field at `path` equals `value`,
+    /// or `None` if there is no such index.
+    pub fn lookup(&self, app_id: &str, path: &str, value: &serde_json::Value) -> Option<Vec<(String, String)>> {
+        let entries = self.apps.get(app_id)?.get(path)?;
+        Some(entries.get(&encode(value)).map(|holders| holders.iter().cloned().collect()).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_and_update() {
+        let mut data = StoreData::new();
+        data.entry(SYSTEM_PERSONA.to_string()).or_default()
+            .entry(INDEXES_APP.to_string()).or_default()
+            .insert("users".to_string(), json!(["/email"]));
+        data.entry("p1".to_string()).or_default()
+            .entry("users".to_string()).or_default()
+            .insert("u1".to_string(), json!({"email": "a@x.io"}));
+
+        let mut set = IndexSet::build(&data);
+        let pair = |p: &str, k: &str| (p.to_string(), k.to_string());
+        assert_eq!(set.lookup("users", "/email", &json!("a@x.io")), Some(vec![pair("p1", "u1")]));
+        assert_eq!(set.lookup("users", "/name", &json!("a@x.io")), None);
+
+        set.update("p1", "users", "u1", Some(&json!({"email": "a@x.io"})), Some(&json!({"email": "b@x.io"})));
+        assert_eq!(set.lookup("users", "/email", &json!("a@x.io")), Some(vec![]));
+        assert_eq!(set.lookup("users", "/email", &json!("b@x.io")), Some(vec![pair("p1", "u1")]));
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Suffix of the sidecar app that holds per-key metadata of an app.
+pub const KEY_META_SUFFIX: &str = "::meta";
+
+/// Returns the ID of the sidecar app holding the key metadata of `app_id`.
+pub fn key_meta_app(app_id: &str) -> String {
+    format!("{}{}", app_id, KEY_META_SUFFIX)
+}
+
+/// Returns whether writes to `app_id` are tracked with metadata.
+///
+/// Sidecar apps (queues, locks, indexes, the metadata itself) are internal
+/// bookkeeping and are not tracked.
+pub fn is_tracked(app_id: &str) -> bool {
+    !app_id.contains("::")
+}
+
+/// The persisted metadata of a single key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyMeta {
+    /// Number of writes that changed the value.
+    pub version: u64,
+    /// Milliseconds since the Unix epoch at which the key was first written.
+    pub created_at: u64,
+    /// Milliseconds since the Unix epoch of the last change.
+    pub updated_at: u64,
+}
+
+impl KeyMeta {
+    /// Loads metadata from its stored JSON value; a missing or unreadable value is treated as untracked.
+    pub fn from_value(value: Option<&serde_json::Value>) -> Option<Self> {
+        value.and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Returns the metadata after one more write at `now`.
+    pub fn bumped(previous: Option<Self>, now: u64) -> Self {
+        match previous {
+            Some(meta) => Self { version: meta.version + 1, created_at: meta.created_at, updated_at: now },
+            None => Self { version: 1, created_at: now, updated_at: now },
+        }
+    }
+
+    /// Serializes the metadata for storage.
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bumped_keeps_creation_time() {
+        let first = KeyMeta::bumped(None, 100);
+        assert_eq!(first, KeyMeta { version: 1, created_at: 100, updated_at: 100 });
+
+        let second = KeyMeta::bumped(Some(first), 250);
+        assert_eq!(second, KeyMeta { version: 2, created_at: 100, updated_at: 250 });
+        assert!(!is_tracked(&key_meta_app("app1")));
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{BTreeMap, HashSet};
+use crate::{Result, Error, SYSTEM_PERSONA, EffectiveValue};
+use crate::engine::memstore::StoreData;
+
+/// App within [`SYSTEM_PERSONA`] that maps each persona ID to its list of parent personas.
+pub const PARENTS_APP: &str = "_parents";
+
+/// Reads the parent list stored for a persona.
+pub fn parents_of(data: &StoreData, persona_id: &str) -> Vec<String> {
+    data.get(SYSTEM_PERSONA)
+        .and_then(|p| p.get(PARENTS_APP))
+        .and_then(|a| a.get(persona_id))
+        .and_then(|v| v.as_array())
+        .map(|list| list.iter().filter_map(|p| p.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the lookup order for a persona: itself, its parents depth-first, then [`SYSTEM_PERSONA`].
+///
+/// Each persona appears once, so cycles in the parent configuration are harmless.
+pub fn chain(data: &StoreData, persona_id: &str) -> Vec<String> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    visit(data, persona_id, &mut order, &mut seen);
+    if seen.insert(SYSTEM_PERSONA.to_string()) {
+        order.push(SYSTEM_PERSONA.to_string());
+    }
+    order
+}
+
+fn visit(data: &StoreData, persona_id: &str, order: &mut Vec<String>, seen: &mut HashSet<String>) {
+    // The system persona is always the final fallback, never an intermediate layer
+    if persona_id == SYSTEM_PERSONA && !order.is_empty() {
+        return;
+    }
+    if !seen.insert(persona_id.to_string()) {
+        return;
+    }
+    order.push(persona_id.to_string());
+    for parent in parents_of(data, persona_id) {
+        visit(data, &parent, order, seen);
+    }
+}
+
+/// Resolves a value from `layers`, ordered from most to least specific.
+///
+/// Without `merge` the most specific layer wins outright. With `merge`, JSON objects
+/// are merged field by field, with more specific layers overriding; any other value
+/// replaces what the layers below provided.
+pub fn resolve(layers: &[(String, &serde_json::Value)], merge: bool) -> Result<EffectiveValue> {
+    let (top_persona, top_value) = layers.first().ok_or(Error::KeyNotFound)?;
+    if !merge {
+        return Ok(EffectiveValue {
+            value: (*top_value).clone(),
+            sources: BTreeMap::from([(String::new(), top_persona.clone())]),
+        });
+    }
+
+    let mut value = serde_json::Value::Null;
+    let mut sources = BTreeMap::new();
+    for (persona_id, layer) in layers.iter().rev() {
+        merge_layer(&mut value, &mut sources, String::new(), layer, persona_id);
+    }
+    Ok(EffectiveValue { value, sources })
+}
+
+fn merge_layer(base: &mut serde_json::Value, sources: &mut BTreeMap<String, String>, path: String, overlay: &serde_json::Value, persona_id: &str) {
+    if let (Some(base_map), Some(overlay_map)) = (base.as_object_mut(), overlay.as_object()) {
+        for (field, v) in overlay_map {
+            let child = child_path(&path, field);
+            match base_map.get_mut(field) {
+                Some(existing) => merge_layer(existing, sources, child, v, persona_id),
+                None => {
+                    base_map.insert(field.clone(), v.clone());
+                    record(sources, child, v, persona_id);
+                }
+            }
+        }
+        return;
+    }
+
+    *base = overlay.clone();
+    let prefix = format!("{}/", path);
+    sources.retain(|p, _| p != &path && !p.starts_with(&prefix));
+    record(sources, path, overlay, persona_id);
+}
+
+/// Appends an escaped object field to a JSON pointer.
+fn child_path(path: &str, field: &str) -> String {
+    format!("{}/{}", path, field.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Records `persona_id` as the source of every leaf field of `value` under `path`.
+fn record(sources: &mut BTreeMap<String, String>, path: String, value: &serde_json::Value, persona_id: &str) {
+    match value.as_object() {
+        Some(map) if !map.is_empty() => {
+            for (field, v) in map {
+                let child = child_path(&path, field);
+                record(sources, child, v, persona_id);
+            }
+        }
+        _ => {
+            sources.insert(path, persona_id.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_tracks_sources() {
+        let tenant = json!({"theme": {"color": "red"}, "limits": 5});
+        let system = json!({"theme": {"color": "blue", "font": "sans"}, "limits": {"max": 10}, "lang": "en"});
+        let layers = vec![("tenant".to_string(), &tenant), (SYSTEM_PERSONA.to_string(), &system)];
+
+        let merged = resolve(&layers, true).unwrap();
+        assert_eq!(merged.value, json!({"theme": {"color": "red", "font": "sans"}, "limits": 5, "lang": "en"}));
+        assert_eq!(merged.sources["/theme/color"], "tenant");
+        assert_eq!(merged.sources["/theme/font"], SYSTEM_PERSONA);
+        assert_eq!(merged.sources["/limits"], "tenant");
+        assert!(!merged.sources.contains_key("/limits/max"));
+
+        let top = resolve(&layers, false).unwrap();
+        assert_eq!(top.value, tenant);
+        assert_eq!(top.sources[""], "tenant");
+    }
+}
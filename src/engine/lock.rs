@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use crate::{Result, Error, Lease};
+
+/// Suffix of the sidecar app that holds the locks of an app.
+pub const LOCK_SUFFIX: &str = "::locks";
+
+/// Returns the ID of the sidecar app holding the locks of `app_id`.
+pub fn lock_app(app_id: &str) -> String {
+    format!("{}{}", app_id, LOCK_SUFFIX)
+}
+
+/// The persisted state of a single named lock.
+/// 
+/// The record outlives releases so that fencing tokens keep increasing for
+/// the lifetime of the lock name, even across restarts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LockState {
+    /// The most recently issued fencing token.
+    token: u64,
+    /// Milliseconds since the Unix epoch at which the current lease ends.
+    /// A value in the past means the lock is free.
+    expires_at: u64,
+}
+
+impl LockState {
+    /// Loads a lock from its stored JSON value; a missing value is a free lock.
+    pub fn from_value(value: Option<&serde_json::Value>) -> Result<Self> {
+        match value {
+            None => Ok(Self::default()),
+            Some(v) => serde_json::from_value(v.clone()).map_err(|_| Error::WrongType("value is not a lock".to_string())),
+        }
+    }
+
+    /// Serializes the lock for storage.
+    pub fn to_value(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    /// Takes the lock if it is free or its lease has expired.
+    pub fn acquire(&mut self, now: u64, ttl_ms: u64) -> Option<Lease> {
+        if self.expires_at > now {
+            return None;
+        }
+        self.token += 1;
+        self.expires_at = now + ttl_ms;
+        Some(self.lease())
+    }
+
+    /// Extends the lease identified by `token`.
+    pub fn renew(&mut self, token: u64, now: u64, ttl_ms: u64) -> Result<Lease> {
+        if token != self.token || self.expires_at <= now {
+            return Err(Error::LockNotHeld);
+        }
+        self.expires_at = now + ttl_ms;
+        Ok(self.lease())
+    }
+
+    /// Ends the lease identified by `token`. Releasing an already expired lease is a no-op.
+    pub fn release(&mut self, token: u64, now: u64) -> Result<()> {
+        if token != self.token {
+            return Err(Error::LockNotHeld);
+        }
+        self.expires_at = self.expires_at.min(now);
+        Ok(())
+    }
+
+    fn lease(&self) -> Lease {
+        Lease { token: self.token, expires_at: self.expires_at }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fencing_tokens_increase() {
+        let mut lock = LockState::default();
+        let first = lock.acquire(1000, 100).unwrap();
+        assert!(lock.acquire(1050, 100).is_none());
+
+        // Expired leases can be taken over, with a higher token
+        let second = lock.acquire(1100, 100).unwrap();
+        assert!(second.token > first.token);
+        assert!(matches!(lock.renew(first.token, 1150, 100), Err(Error::LockNotHeld)));
+        assert_eq!(lock.renew(second.token, 1150, 100).unwrap().expires_at, 1250);
+
+        lock.release(second.token, 1160).unwrap();
+        let third = lock.acquire(1160, 100).unwrap();
+        assert!(third.token > second.token);
+    }
+}
@@ -1,21 +1,52 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use async_trait::async_trait;
-use crate::{Result, Error, KVReader, KVWriter, AppEnumeration, BatchExporter, GlobalSearcher, Orchestrator, CelerixStore, AppScope, VaultScope};
-use crate::engine::{Persistence, vault};
+use tokio::sync::Notify;
+use crate::{Result, Error, SYSTEM_PERSONA, HistoryEntry, Patch, ListEnd, QueueMessage, Lease, KeyStat, EffectiveValue, Query, Aggregation, AggregateGroup, SearchHit, KVReader, KVWriter, VaultStorage, ListOperations, SetOperations, QueueOperations, LockManager, AppEnumeration, BatchExporter, GlobalSearcher, LayeredConfig, IndexManager, TextSearch, SchemaRegistry, KeyHistory, TrashBin, TrashEntry, DerivedKeys, Derivation, DerivedKey, KeyRef, Orchestrator, CelerixStore, AppScope, VaultScope};
+use crate::engine::{Persistence, derived, fulltext, history, index, keymeta, layers, lock, queue, schema, trash, vault};
+use crate::engine::derived::DerivedSet;
+use crate::engine::fulltext::TextIndex;
+use crate::engine::history::HistoryRetention;
+use crate::engine::hooks::{AfterHook, ChangeEvent, ChangeKind, HookScope, Hooks};
+use crate::engine::index::IndexSet;
+use crate::engine::keymeta::KeyMeta;
+use crate::engine::lock::LockState;
+use crate::engine::queue::QueueState;
+use crate::engine::trash::Trashed;
+use crate::engine::{belongs_to_app, check_app_id, check_kv_app_id, unix_millis};
+use crate::engine::vault::{SecretBytes, SecretString, VaultEntryMeta};
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-type StoreData = HashMap<String, HashMap<String, HashMap<String, serde_json::Value>>>;
+/// Key-value pairs of a single persona, grouped by app ID.
+pub type PersonaData = HashMap<String, HashMap<String, serde_json::Value>>;
+/// The complete store contents, keyed by persona ID.
+pub type StoreData = HashMap<String, PersonaData>;
 
 /// A thread-safe, in-memory implementation of the [`CelerixStore`] trait.
 /// 
 /// `MemStore` maintains all data in memory for high-performance concurrent access
 /// and supports asynchronous persistence to JSON files.
 pub struct MemStore {
-    data: RwLock<StoreData>,
+    data: Arc<RwLock<StoreData>>,
+    indexes: RwLock<IndexSet>,
+    text_index: RwLock<TextIndex>,
+    derived: RwLock<DerivedSet>,
+    /// Personas changed by derived keys since the last persist.
+    derived_dirty: Mutex<HashSet<String>>,
+    /// After hooks of derived keys recomputed since the last notify.
+    derived_changes: Mutex<Vec<(ChangeEvent, Vec<AfterHook>)>>,
     persistence: Option<Arc<Persistence>>,
     pending_tasks: Arc<AtomicUsize>,
+    /// Personas with a background save that has not taken its snapshot yet.
+    scheduled: Arc<Mutex<HashSet<String>>>,
+    save_locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    queue_notify: Notify,
+    queue_max_attempts: u32,
+    history: HistoryRetention,
+    trash: Option<Duration>,
+    hooks: RwLock<Hooks>,
 }
 
 impl MemStore {
@@ -25,12 +56,77 @@ impl MemStore {
     /// - `persistence`: Optional persistence handler for background saves.
     pub fn new(initial_data: StoreData, persistence: Option<Arc<Persistence>>) -> Self {
         Self {
-            data: RwLock::new(initial_data),
+            indexes: RwLock::new(IndexSet::build(&initial_data)),
+            text_index: RwLock::new(TextIndex::build(&initial_data)),
+            derived: RwLock::new(DerivedSet::build(&initial_data)),
+            derived_dirty: Mutex::new(HashSet::new()),
+            derived_changes: Mutex::new(Vec::new()),
+            data: Arc::new(RwLock::new(initial_data)),
             persistence,
             pending_tasks: Arc::new(AtomicUsize::new(0)),
+            scheduled: Arc::new(Mutex::new(HashSet::new())),
+            save_locks: Mutex::new(HashMap::new()),
+            queue_notify: Notify::new(),
+            queue_max_attempts: queue::DEFAULT_MAX_ATTEMPTS,
+            history: HistoryRetention::default(),
+            trash: None,
+            hooks: RwLock::new(Hooks::default()),
         }
     }
 
+    /// Sets how many times a queue message is delivered before it is dead-lettered.
+    /// 
+    /// Defaults to [`queue::DEFAULT_MAX_ATTEMPTS`].
+    pub fn with_queue_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.queue_max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets how many past versions of each key are kept.
+    /// 
+    /// History is off by default, as if set to `HistoryRetention::Versions(0)`.
+    pub fn with_history(mut self, retention: HistoryRetention) -> Self {
+        self.history = retention;
+        self
+    }
+
+    /// Makes [`KVWriter::delete`] move keys into the persona's trash, where they can be
+    /// restored until `retention` has passed.
+    /// 
+    /// Without a trash, which is the default, deletes are final.
+    pub fn with_trash(mut self, retention: Duration) -> Self {
+        self.trash = Some(retention);
+        self
+    }
+
+    /// Registers a hook that can transform or reject values about to be stored within `scope`.
+    /// 
+    /// It runs for every write that stores a value in a regular app, including patches and
+    /// counters, before schema validation. Returning an error rejects the write. The hook
+    /// runs while the store is locked, so it must not call back into the store. Whole apps
+    /// and personas that are moved, copied or renamed are not passed through it, and
+    /// neither are recomputed derived keys.
+    pub fn on_before_set(&self, scope: HookScope, hook: impl Fn(&str, &str, &str, serde_json::Value) -> Result<serde_json::Value> + Send + Sync + 'static) {
+        self.hooks.write().unwrap().add_before_set(scope, Arc::new(hook));
+    }
+
+    /// Registers a hook run after a key within `scope` was written.
+    /// 
+    /// It runs on the writing task once the store is unlocked, so slow side effects should be spawned.
+    /// Besides single-key writes, it runs for every key that `copy_persona`, `rename_persona`
+    /// and `move_app` put in place, and for every derived key that is recomputed.
+    pub fn on_after_set(&self, scope: HookScope, hook: impl Fn(&ChangeEvent) + Send + Sync + 'static) {
+        self.hooks.write().unwrap().add_after_set(scope, Arc::new(hook));
+    }
+
+    /// Registers a hook run after a key within `scope` was deleted, including moves into the trash.
+    /// 
+    /// It also runs for every key removed by `delete_app`, `delete_persona`, `rename_persona`,
+    /// `move_app` and `drop_derived`.
+    pub fn on_after_delete(&self, scope: HookScope, hook: impl Fn(&ChangeEvent) + Send + Sync + 'static) {
+        self.hooks.write().unwrap().add_after_delete(scope, Arc::new(hook));
+    }
+
     /// Waits for all background persistence tasks to complete.
     /// 
     /// This is useful during graceful shutdown to ensure no data is lost.
@@ -40,26 +136,330 @@ impl MemStore {
         }
     }
 
-    fn copy_persona_data(&self, persona_id: &str) -> Option<HashMap<String, HashMap<String, serde_json::Value>>> {
-        let data = self.data.read().unwrap();
-        data.get(persona_id).cloned()
+    /// Applies `f` to the current value of a key while holding the write lock.
+    /// 
+    /// `f` receives the current value (if any) and returns the new value, where
+    /// `None` removes the key, along with a result for the caller. The persona
+    /// is persisted once the lock has been released. If `f` fails or leaves the
+    /// value unchanged, nothing is written. New values must satisfy the app's
+    /// schema, if one is registered.
+    async fn update<R, F>(&self, persona_id: &str, app_id: &str, key: &str, f: F) -> Result<R>
+    where
+        F: FnOnce(Option<&serde_json::Value>) -> Result<(Option<serde_json::Value>, R)> + Send,
+    {
+        self.update_checked(persona_id, app_id, key, true, f).await
     }
 
-    async fn persist(&self, persona_id: String) {
-        if let Some(p) = &self.persistence {
-            if let Some(persona_data) = self.copy_persona_data(&persona_id) {
-                let p = p.clone();
-                let pending = self.pending_tasks.clone();
-                pending.fetch_add(1, Ordering::SeqCst);
-                tokio::task::spawn_blocking(move || {
-                    if let Err(e) = p.save_persona(&persona_id, &persona_data) {
-                        log::error!("Failed to persist persona {}: {}", persona_id, e);
+    /// Like [`Self::update`], for the store's own writes to a [`CONFIG_APPS`] entry,
+    /// which callers may not write directly.
+    async fn update_config<R, F>(&self, app_id: &str, key: &str, f: F) -> Result<R>
+    where
+        F: FnOnce(Option<&serde_json::Value>) -> Result<(Option<serde_json::Value>, R)> + Send,
+    {
+        self.update_checked(SYSTEM_PERSONA, app_id, key, false, f).await
+    }
+
+    async fn update_checked<R, F>(&self, persona_id: &str, app_id: &str, key: &str, guarded: bool, f: F) -> Result<R>
+    where
+        F: FnOnce(Option<&serde_json::Value>) -> Result<(Option<serde_json::Value>, R)> + Send,
+    {
+        let (out, change) = {
+            let mut data = self.data.write().unwrap();
+            if guarded {
+                self.check_writable(persona_id, app_id, key)?;
+            }
+            let current = data.get(persona_id)
+                .and_then(|p| p.get(app_id))
+                .and_then(|a| a.get(key));
+            let (new_value, out) = f(current)?;
+            let new_value = match new_value {
+                Some(value) => Some(self.run_before_set(persona_id, app_id, key, value)?),
+                None => None,
+            };
+            if new_value.as_ref() == current {
+                return Ok(out);
+            }
+            if let Some(value) = &new_value {
+                schema::enforce(&data, app_id, value)?;
+            }
+
+            let change = self.pending_change(persona_id, app_id, key, new_value.as_ref());
+            self.write_value(&mut data, persona_id, app_id, key, new_value);
+            (out, change)
+        };
+        self.persist(persona_id.to_string()).await;
+        self.notify(change);
+        Ok(out)
+    }
+
+    /// Runs the before-set hooks on a value about to be stored. Sidecar apps are not hooked.
+    fn run_before_set(&self, persona_id: &str, app_id: &str, key: &str, value: serde_json::Value) -> Result<serde_json::Value> {
+        if !keymeta::is_tracked(app_id) {
+            return Ok(value);
+        }
+        self.hooks.read().unwrap().before_set(persona_id, app_id, key, value)
+    }
+
+    /// Fails with [`Error::ReadOnly`] if a key is only written by the store itself: a
+    /// [`CONFIG_APPS`] entry, or a derived value.
+    fn check_writable(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        check_config_app(persona_id, app_id)?;
+        if self.derived.read().unwrap().is_target(persona_id, app_id, key) {
+            return Err(Error::ReadOnly(format!("{} is a derived key", derived::definition_key(&KeyRef::new(persona_id, app_id, key)))));
+        }
+        Ok(())
+    }
+
+    /// Collects the after hooks of a change, with `None` for a delete, so they can run once the lock is released.
+    fn pending_change(&self, persona_id: &str, app_id: &str, key: &str, value: Option<&serde_json::Value>) -> Option<(ChangeEvent, Vec<AfterHook>)> {
+        if !keymeta::is_tracked(app_id) {
+            return None;
+        }
+        let kind = if value.is_some() { ChangeKind::Set } else { ChangeKind::Delete };
+        let hooks = self.hooks.read().unwrap().after(kind, persona_id, app_id, key);
+        (!hooks.is_empty()).then(|| (ChangeEvent {
+            kind,
+            persona_id: persona_id.to_string(),
+            app_id: app_id.to_string(),
+            key: key.to_string(),
+            value: value.cloned(),
+        }, hooks))
+    }
+
+    /// Collects the after hooks for every key of a persona's apps, or only of `app_id`,
+    /// as written (`removed == false`) or removed by a whole-app or whole-persona change.
+    fn pending_app_changes(&self, persona_id: &str, persona: &PersonaData, app_id: Option<&str>, removed: bool) -> Vec<(ChangeEvent, Vec<AfterHook>)> {
+        persona.iter()
+            .filter(|(name, _)| app_id.is_none_or(|a| a == name.as_str()))
+            .flat_map(|(name, app)| app.iter().map(move |(key, value)| (name, key, value)))
+            .filter_map(|(name, key, value)| self.pending_change(persona_id, name, key, (!removed).then_some(value)))
+            .collect()
+    }
+
+    /// Runs the after hooks of `changes`, then those of the derived keys they recomputed.
+    fn notify(&self, changes: impl IntoIterator<Item = (ChangeEvent, Vec<AfterHook>)>) {
+        let derived = std::mem::take(&mut *self.derived_changes.lock().unwrap());
+        for (event, hooks) in changes.into_iter().chain(derived) {
+            for hook in hooks {
+                hook(&event);
+            }
+        }
+    }
+
+    /// Stores or, for `None`, removes a single value in the in-memory data.
+    /// 
+    /// This is the single place where values change, so it also keeps the key
+    /// metadata and history of tracked apps, the secondary indexes and derived
+    /// keys in step. Callers hold the data write lock.
+    fn write_value(&self, data: &mut StoreData, persona_id: &str, app_id: &str, key: &str, new_value: Option<serde_json::Value>) {
+        self.write_value_at(data, persona_id, app_id, key, new_value, 0);
+    }
+
+    /// Does the work of [`Self::write_value`], where `depth` counts the derived keys that led to this write.
+    fn write_value_at(&self, data: &mut StoreData, persona_id: &str, app_id: &str, key: &str, new_value: Option<serde_json::Value>, depth: usize) {
+        let meta_app = keymeta::key_meta_app(app_id);
+        let now = unix_millis();
+        let mut indexes = self.indexes.write().unwrap();
+        let mut text_index = self.text_index.write().unwrap();
+        let old_value = data.get(persona_id).and_then(|p| p.get(app_id)).and_then(|a| a.get(key));
+        indexes.update(persona_id, app_id, key, old_value, new_value.as_ref());
+        text_index.update(persona_id, app_id, key, old_value, new_value.as_ref());
+        let existed = old_value.is_some();
+
+        let defined = (persona_id == SYSTEM_PERSONA && app_id == derived::DERIVED_APP)
+            .then(|| new_value.as_ref().and_then(|v| serde_json::from_value::<DerivedKey>(v.clone()).ok()))
+            .flatten();
+
+        // A delete is recorded as a tombstone, so the log survives it and retention trims it like any other version
+        if keymeta::is_tracked(app_id) && self.history.is_enabled() && (new_value.is_some() || existed) {
+            let entry = match &new_value {
+                Some(value) => HistoryEntry { at: now, value: value.clone(), deleted: false },
+                None => HistoryEntry { at: now, value: serde_json::Value::Null, deleted: true },
+            };
+            let log = data.entry(persona_id.to_string()).or_default().entry(history::history_app(app_id)).or_default();
+            let updated = history::record(log.get(key), entry, self.history);
+            log.insert(key.to_string(), updated);
+        }
+
+        // Vault metadata and blind indexes describe one write; `vault_set` records them again afterwards
+        if keymeta::is_tracked(app_id) {
+            if let Some(persona) = data.get_mut(persona_id) {
+                for sidecar in [vault::meta_app(app_id), vault::blind_index_app(app_id)] {
+                    if let Some(entries) = persona.get_mut(&sidecar) {
+                        entries.remove(key);
+                    }
+                }
+            }
+        }
+
+        match new_value {
+            Some(value) => {
+                let persona = data.entry(persona_id.to_string()).or_default();
+                if keymeta::is_tracked(app_id) {
+                    let meta_data = persona.entry(meta_app).or_default();
+                    let meta = KeyMeta::bumped(KeyMeta::from_value(meta_data.get(key)), now);
+                    meta_data.insert(key.to_string(), meta.to_value());
+                }
+                let app = persona.entry(app_id.to_string()).or_default();
+                app.insert(key.to_string(), value);
+            }
+            None => {
+                if let Some(persona) = data.get_mut(persona_id) {
+                    if let Some(app) = persona.get_mut(app_id) {
+                        app.remove(key);
+                    }
+                    if keymeta::is_tracked(app_id) {
+                        if let Some(meta_data) = persona.get_mut(&meta_app) {
+                            meta_data.remove(key);
+                        }
                     }
-                    pending.fetch_sub(1, Ordering::SeqCst);
-                });
+                }
+            }
+        }
+        if persona_id == SYSTEM_PERSONA && app_id == index::INDEXES_APP {
+            *indexes = IndexSet::build(data);
+        }
+        if persona_id == SYSTEM_PERSONA && app_id == fulltext::FULLTEXT_APP {
+            *text_index = TextIndex::build(data);
+        }
+        // Derived keys are written through here again, so the index locks must be released first
+        drop(indexes);
+        drop(text_index);
+
+        if persona_id == SYSTEM_PERSONA && app_id == derived::DERIVED_APP {
+            *self.derived.write().unwrap() = DerivedSet::build(data);
+        }
+        let affected = self.derived.read().unwrap().affected(persona_id, app_id, key);
+        for def in defined.iter().chain(&affected) {
+            self.recompute_derived(data, def, depth + 1);
+        }
+    }
+
+    /// Recomputes a derived key and stores the result if it changed. Callers hold the data write lock.
+    fn recompute_derived(&self, data: &mut StoreData, def: &DerivedKey, depth: usize) {
+        let target = &def.target;
+        if depth > derived::MAX_DEPTH {
+            log::warn!("Derived key {} not updated: too many derived keys feed into each other", derived::definition_key(target));
+            return;
+        }
+        // A value the target app's schema rejects is not stored, so readers never see it
+        let value = derived::compute(data, &def.derivation).filter(|value| match schema::enforce(data, &target.app_id, value) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Derived key {} removed: {}", derived::definition_key(target), e);
+                false
             }
+        });
+        let current = data.get(&target.persona_id).and_then(|p| p.get(&target.app_id)).and_then(|a| a.get(&target.key));
+        if value.as_ref() == current {
+            return;
+        }
+        self.derived_dirty.lock().unwrap().insert(target.persona_id.clone());
+        self.derived_changes.lock().unwrap().extend(self.pending_change(&target.persona_id, &target.app_id, &target.key, value.as_ref()));
+        self.write_value_at(data, &target.persona_id, &target.app_id, &target.key, value, depth);
+    }
+
+    /// Rebuilds the indexes and recomputes all derived keys after a bulk change. Callers hold the data write lock.
+    fn rebuild_indexes(&self, data: &mut StoreData) {
+        *self.indexes.write().unwrap() = IndexSet::build(data);
+        *self.text_index.write().unwrap() = TextIndex::build(data);
+        *self.derived.write().unwrap() = DerivedSet::build(data);
+        for def in derived::definitions(data) {
+            self.recompute_derived(data, &def, 1);
         }
     }
+
+    /// Runs `f` on the current value of a key while holding the read lock.
+    fn read_value<R>(&self, persona_id: &str, app_id: &str, key: &str, f: impl FnOnce(Option<&serde_json::Value>) -> Result<R>) -> Result<R> {
+        let data = self.data.read().unwrap();
+        let current = data.get(persona_id)
+            .and_then(|p| p.get(app_id))
+            .and_then(|a| a.get(key));
+        f(current)
+    }
+
+    /// Saves a persona in the background, or removes its file if the persona has no data left.
+    async fn persist(&self, persona_id: String) {
+        // Derived keys may have changed other personas along with this one
+        let mut personas = std::mem::take(&mut *self.derived_dirty.lock().unwrap());
+        personas.insert(persona_id);
+        for persona_id in personas {
+            self.save(persona_id);
+        }
+    }
+
+    /// Snapshots a persona and writes it to disk in the background.
+    /// 
+    /// Saves are coalesced: while a save of the persona is waiting to run, further
+    /// changes are picked up by it instead of scheduling another one.
+    fn save(&self, persona_id: String) {
+        let Some(p) = &self.persistence else { return };
+        if !self.scheduled.lock().unwrap().insert(persona_id.clone()) {
+            return;
+        }
+        let p = p.clone();
+        let data = self.data.clone();
+        let scheduled = self.scheduled.clone();
+        let pending = self.pending_tasks.clone();
+        let save_lock = self.save_locks.lock().unwrap().entry(persona_id.clone()).or_default().clone();
+        pending.fetch_add(1, Ordering::SeqCst);
+        tokio::task::spawn_blocking(move || {
+            // Saves of a persona run one at a time, and each snapshots the data as of its
+            // start, so an older snapshot never overwrites a newer one
+            let _guard = save_lock.lock().unwrap();
+            scheduled.lock().unwrap().remove(&persona_id);
+            let persona_data = copy_persona_data(&data.read().unwrap(), &persona_id);
+            let res = match &persona_data {
+                Some(data) => p.save_persona(&persona_id, data),
+                None => p.delete_persona(&persona_id),
+            };
+            if let Err(e) = res {
+                log::error!("Failed to persist persona {}: {}", persona_id, e);
+            }
+            pending.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+/// Apps of [`SYSTEM_PERSONA`] holding store configuration, with the method that maintains each.
+///
+/// The store rebuilds its indexes and checks values from these entries, so a malformed
+/// one written directly could break every later write.
+const CONFIG_APPS: &[(&str, &str)] = &[
+    (schema::SCHEMAS_APP, "set_schema"),
+    (index::INDEXES_APP, "create_index"),
+    (fulltext::FULLTEXT_APP, "enable_search"),
+    (layers::PARENTS_APP, "set_parents"),
+    (derived::DERIVED_APP, "define_derived"),
+];
+
+/// Fails with [`Error::ReadOnly`] if `app_id` is one of the [`CONFIG_APPS`].
+fn check_config_app(persona_id: &str, app_id: &str) -> Result<()> {
+    match CONFIG_APPS.iter().find(|(app, _)| persona_id == SYSTEM_PERSONA && *app == app_id) {
+        Some((app, method)) => Err(Error::ReadOnly(format!("{}/{} is maintained through {}", SYSTEM_PERSONA, app, method))),
+        None => Ok(()),
+    }
+}
+
+/// Copies a persona's non-empty apps, or `None` if it has no data left.
+fn copy_persona_data(data: &StoreData, persona_id: &str) -> Option<PersonaData> {
+    let persona: PersonaData = data.get(persona_id)
+        .map(|p| p.iter()
+            .filter(|(_, app)| !app.is_empty())
+            .map(|(name, app)| (name.clone(), app.clone()))
+            .collect())
+        .unwrap_or_default();
+    (!persona.is_empty()).then_some(persona)
+}
+
+/// Returns whether any app of `persona` holds data.
+fn persona_has_data(persona: &PersonaData) -> bool {
+    persona.values().any(|app| !app.is_empty())
+}
+
+/// Returns whether `app_id` or any of its sidecar apps holds data in `persona`.
+fn app_has_data(persona: &PersonaData, app_id: &str) -> bool {
+    persona.iter().any(|(name, app)| belongs_to_app(name, app_id) && !app.is_empty())
 }
 
 #[async_trait]
@@ -81,48 +481,397 @@ impl KVReader for MemStore {
             .cloned()
             .ok_or(Error::KeyNotFound)
     }
+
+    async fn get_path(&self, persona_id: &str, app_id: &str, key: &str, path: &str) -> Result<serde_json::Value> {
+        let data = self.data.read().unwrap();
+        data.get(persona_id)
+            .ok_or(Error::PersonaNotFound)?
+            .get(app_id)
+            .ok_or(Error::AppNotFound)?
+            .get(key)
+            .ok_or(Error::KeyNotFound)?
+            .pointer(path)
+            .cloned()
+            .ok_or(Error::PathNotFound)
+    }
+
+    async fn get_many(&self, keys: &[(&str, &str, &str)]) -> Result<Vec<Option<serde_json::Value>>> {
+        let data = self.data.read().unwrap();
+        Ok(keys.iter()
+            .map(|(p, a, k)| data.get(*p).and_then(|p| p.get(*a)).and_then(|a| a.get(*k)).cloned())
+            .collect())
+    }
+
+    async fn exists(&self, persona_id: &str, app_id: &str, key: &str) -> Result<bool> {
+        self.read_value(persona_id, app_id, key, |current| Ok(current.is_some()))
+    }
+
+    async fn stat(&self, persona_id: &str, app_id: &str, key: &str) -> Result<KeyStat> {
+        let data = self.data.read().unwrap();
+        let persona = data.get(persona_id).ok_or(Error::PersonaNotFound)?;
+        let value = persona.get(app_id)
+            .ok_or(Error::AppNotFound)?
+            .get(key)
+            .ok_or(Error::KeyNotFound)?;
+        let meta = KeyMeta::from_value(persona.get(&keymeta::key_meta_app(app_id)).and_then(|m| m.get(key)));
+
+        Ok(KeyStat {
+            size_bytes: serde_json::to_vec(value)?.len() as u64,
+            version: meta.as_ref().map(|m| m.version).unwrap_or(0),
+            created_at: meta.as_ref().map(|m| m.created_at),
+            updated_at: meta.as_ref().map(|m| m.updated_at),
+        })
+    }
 }
 
 #[async_trait]
 impl KVWriter for MemStore {
     async fn set(&self, persona_id: &str, app_id: &str, key: &str, value: serde_json::Value) -> Result<()> {
-        {
+        check_kv_app_id(app_id)?;
+        self.update(persona_id, app_id, key, |_| Ok((Some(value), ()))).await
+    }
+
+    async fn delete(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        check_kv_app_id(app_id)?;
+        // Sidecar apps are bookkeeping and are never trashed
+        let Some(retention) = self.trash.filter(|_| keymeta::is_tracked(app_id)) else {
+            return self.update(persona_id, app_id, key, |_| Ok((None, ()))).await;
+        };
+        let change = {
             let mut data = self.data.write().unwrap();
-            let persona = data.entry(persona_id.to_string()).or_default();
-            let app = persona.entry(app_id.to_string()).or_default();
-            app.insert(key.to_string(), value);
-        }
+            self.check_writable(persona_id, app_id, key)?;
+            let Some(value) = data.get(persona_id).and_then(|p| p.get(app_id)).and_then(|a| a.get(key)).cloned() else {
+                return Ok(());
+            };
+            let now = unix_millis();
+            let trash_app = trash::trash_app(app_id);
+            for expired in trash::expired(&data[persona_id], app_id, retention, now) {
+                self.write_value(&mut data, persona_id, &trash_app, &expired, None);
+            }
+            self.write_value(&mut data, persona_id, app_id, key, None);
+            self.write_value(&mut data, persona_id, &trash_app, key, Some(Trashed { value, deleted_at: now }.to_value()));
+            self.pending_change(persona_id, app_id, key, None)
+        };
         self.persist(persona_id.to_string()).await;
+        self.notify(change);
         Ok(())
     }
 
-    async fn delete(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
-        {
+    async fn set_many(&self, entries: Vec<(&str, &str, &str, serde_json::Value)>) -> Result<()> {
+        let (touched, changes) = {
             let mut data = self.data.write().unwrap();
-            if let Some(persona) = data.get_mut(persona_id) {
-                if let Some(app) = persona.get_mut(app_id) {
-                    app.remove(key);
+            let entries = entries.into_iter()
+                .map(|(p, a, k, value)| {
+                    check_kv_app_id(a)?;
+                    self.check_writable(p, a, k)?;
+                    Ok((p, a, k, self.run_before_set(p, a, k, value)?))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for (_, app_id, _, value) in &entries {
+                schema::enforce(&data, app_id, value)?;
+            }
+            let mut touched = Vec::new();
+            let mut changes = Vec::new();
+            for (persona_id, app_id, key, value) in entries {
+                let current = data.get(persona_id)
+                    .and_then(|p| p.get(app_id))
+                    .and_then(|a| a.get(key));
+                if current == Some(&value) {
+                    continue;
+                }
+                changes.extend(self.pending_change(persona_id, app_id, key, Some(&value)));
+                self.write_value(&mut data, persona_id, app_id, key, Some(value));
+                if !touched.contains(&persona_id) {
+                    touched.push(persona_id);
                 }
             }
+            (touched, changes)
+        };
+        for persona_id in touched {
+            self.persist(persona_id.to_string()).await;
         }
-        self.persist(persona_id.to_string()).await;
+        self.notify(changes);
+        Ok(())
+    }
+
+    async fn incr(&self, persona_id: &str, app_id: &str, key: &str, delta: i64) -> Result<i64> {
+        check_kv_app_id(app_id)?;
+        self.update(persona_id, app_id, key, |current| {
+            let value = match current {
+                None => 0,
+                // A u64 above i64::MAX is still an integer, just one a counter cannot hold
+                Some(v) if v.is_u64() && v.as_i64().is_none() => {
+                    return Err(Error::OutOfRange("value does not fit in a 64-bit signed integer".to_string()));
+                }
+                Some(v) => v.as_i64().ok_or_else(|| Error::WrongType("value is not an integer".to_string()))?,
+            };
+            let value = value.checked_add(delta).ok_or_else(|| Error::OutOfRange("increment would overflow".to_string()))?;
+            Ok((Some(serde_json::Value::from(value)), value))
+        }).await
+    }
+
+    async fn patch(&self, persona_id: &str, app_id: &str, key: &str, patch: Patch) -> Result<serde_json::Value> {
+        check_kv_app_id(app_id)?;
+        self.update(persona_id, app_id, key, |current| {
+            let mut value = current.cloned().unwrap_or(serde_json::Value::Null);
+            patch.apply(&mut value)?;
+            Ok((Some(value.clone()), value))
+        }).await
+    }
+}
+
+/// Borrows a value as a JSON array, treating a missing value as empty.
+fn array_items(value: Option<&serde_json::Value>) -> Result<&[serde_json::Value]> {
+    match value {
+        None => Ok(&[]),
+        Some(serde_json::Value::Array(items)) => Ok(items),
+        Some(_) => Err(Error::WrongType("value is not an array".to_string())),
+    }
+}
+
+#[async_trait]
+impl ListOperations for MemStore {
+    async fn list_push(&self, persona_id: &str, app_id: &str, key: &str, end: ListEnd, value: serde_json::Value) -> Result<usize> {
+        check_app_id(app_id)?;
+        self.update(persona_id, app_id, key, |current| {
+            let mut items = array_items(current)?.to_vec();
+            match end {
+                ListEnd::Front => items.insert(0, value),
+                ListEnd::Back => items.push(value),
+            }
+            let len = items.len();
+            Ok((Some(serde_json::Value::Array(items)), len))
+        }).await
+    }
+
+    async fn list_pop(&self, persona_id: &str, app_id: &str, key: &str, end: ListEnd) -> Result<Option<serde_json::Value>> {
+        check_app_id(app_id)?;
+        self.update(persona_id, app_id, key, |current| {
+            let Some(current) = current else {
+                return Ok((None, None));
+            };
+            let mut items = array_items(Some(current))?.to_vec();
+            let popped = match end {
+                ListEnd::Front if !items.is_empty() => Some(items.remove(0)),
+                ListEnd::Front => None,
+                ListEnd::Back => items.pop(),
+            };
+            Ok((Some(serde_json::Value::Array(items)), popped))
+        }).await
+    }
+
+    async fn list_range(&self, persona_id: &str, app_id: &str, key: &str, start: i64, stop: i64) -> Result<Vec<serde_json::Value>> {
+        self.read_value(persona_id, app_id, key, |current| {
+            let items = array_items(current)?;
+            let len = items.len() as i64;
+            let start = if start < 0 { (len + start).max(0) } else { start };
+            let stop = if stop < 0 { len + stop } else { stop.min(len - 1) };
+            if start > stop || start >= len {
+                return Ok(Vec::new());
+            }
+            Ok(items[start as usize..=stop as usize].to_vec())
+        })
+    }
+
+    async fn list_len(&self, persona_id: &str, app_id: &str, key: &str) -> Result<usize> {
+        self.read_value(persona_id, app_id, key, |current| Ok(array_items(current)?.len()))
+    }
+}
+
+#[async_trait]
+impl SetOperations for MemStore {
+    async fn set_add(&self, persona_id: &str, app_id: &str, key: &str, member: serde_json::Value) -> Result<bool> {
+        check_app_id(app_id)?;
+        self.update(persona_id, app_id, key, |current| {
+            let mut items = array_items(current)?.to_vec();
+            if items.contains(&member) {
+                return Ok((current.cloned(), false));
+            }
+            items.push(member);
+            Ok((Some(serde_json::Value::Array(items)), true))
+        }).await
+    }
+
+    async fn set_remove(&self, persona_id: &str, app_id: &str, key: &str, member: &serde_json::Value) -> Result<bool> {
+        check_app_id(app_id)?;
+        self.update(persona_id, app_id, key, |current| {
+            let mut items = array_items(current)?.to_vec();
+            let before = items.len();
+            items.retain(|m| m != member);
+            let removed = items.len() != before;
+            let new_value = if current.is_some() { Some(serde_json::Value::Array(items)) } else { None };
+            Ok((new_value, removed))
+        }).await
+    }
+
+    async fn set_members(&self, persona_id: &str, app_id: &str, key: &str) -> Result<Vec<serde_json::Value>> {
+        self.read_value(persona_id, app_id, key, |current| Ok(array_items(current)?.to_vec()))
+    }
+
+    async fn set_contains(&self, persona_id: &str, app_id: &str, key: &str, member: &serde_json::Value) -> Result<bool> {
+        self.read_value(persona_id, app_id, key, |current| Ok(array_items(current)?.contains(member)))
+    }
+}
+
+impl MemStore {
+    /// Attempts a single non-blocking dequeue.
+    /// 
+    /// Returns the delivered message, or when the next in-flight message becomes
+    /// visible again so a blocked consumer knows when to retry.
+    async fn try_dequeue(&self, persona_id: &str, queue_app: &str, queue: &str, visibility_ms: u64) -> Result<(Option<QueueMessage>, Option<u64>)> {
+        let max_attempts = self.queue_max_attempts;
+        self.update(persona_id, queue_app, queue, |current| {
+            if current.is_none() {
+                return Ok((None, (None, None)));
+            }
+            let mut state = QueueState::from_value(current)?;
+            let now = unix_millis();
+            let msg = state.dequeue(now, visibility_ms, max_attempts);
+            let next_visible = state.next_visible_at(now);
+            Ok((Some(state.to_value()?), (msg, next_visible)))
+        }).await
+    }
+}
+
+#[async_trait]
+impl QueueOperations for MemStore {
+    async fn enqueue(&self, persona_id: &str, app_id: &str, queue: &str, body: serde_json::Value) -> Result<u64> {
+        check_app_id(app_id)?;
+        let id = self.update(persona_id, &queue::queue_app(app_id), queue, |current| {
+            let mut state = QueueState::from_value(current)?;
+            let id = state.enqueue(body);
+            Ok((Some(state.to_value()?), id))
+        }).await?;
+        self.queue_notify.notify_waiters();
+        Ok(id)
+    }
+
+    async fn dequeue(&self, persona_id: &str, app_id: &str, queue: &str, visibility: Duration, wait: Duration) -> Result<Option<QueueMessage>> {
+        check_app_id(app_id)?;
+        let queue_app = queue::queue_app(app_id);
+        let visibility_ms = visibility.as_millis() as u64;
+        let deadline = tokio::time::Instant::now() + wait;
+
+        loop {
+            // Register for wake-ups before checking, so an enqueue in between is not missed
+            let notified = self.queue_notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let (msg, next_visible) = self.try_dequeue(persona_id, &queue_app, queue, visibility_ms).await?;
+            if msg.is_some() {
+                return Ok(msg);
+            }
+
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let mut sleep_until = deadline;
+            if let Some(at) = next_visible {
+                let until_visible = Duration::from_millis(at.saturating_sub(unix_millis()));
+                sleep_until = sleep_until.min(now + until_visible);
+            }
+
+            tokio::select! {
+                _ = &mut notified => {}
+                _ = tokio::time::sleep_until(sleep_until) => {}
+            }
+        }
+    }
+
+    async fn ack(&self, persona_id: &str, app_id: &str, queue: &str, receipt: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update(persona_id, &queue::queue_app(app_id), queue, |current| {
+            let mut state = QueueState::from_value(current)?;
+            state.ack(receipt)?;
+            Ok((Some(state.to_value()?), ()))
+        }).await
+    }
+
+    async fn nack(&self, persona_id: &str, app_id: &str, queue: &str, receipt: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        let max_attempts = self.queue_max_attempts;
+        self.update(persona_id, &queue::queue_app(app_id), queue, |current| {
+            let mut state = QueueState::from_value(current)?;
+            state.nack(receipt, max_attempts)?;
+            Ok((Some(state.to_value()?), ()))
+        }).await?;
+        self.queue_notify.notify_waiters();
         Ok(())
     }
+
+    async fn dead_letters(&self, persona_id: &str, app_id: &str, queue: &str) -> Result<Vec<QueueMessage>> {
+        self.read_value(persona_id, &queue::queue_app(app_id), queue, |current| {
+            Ok(QueueState::from_value(current)?.dead_letters())
+        })
+    }
+}
+
+#[async_trait]
+impl LockManager for MemStore {
+    async fn acquire_lock(&self, persona_id: &str, app_id: &str, name: &str, ttl: Duration) -> Result<Option<Lease>> {
+        check_app_id(app_id)?;
+        self.update(persona_id, &lock::lock_app(app_id), name, |current| {
+            let mut state = LockState::from_value(current)?;
+            let lease = state.acquire(unix_millis(), ttl.as_millis() as u64);
+            Ok((Some(state.to_value()?), lease))
+        }).await
+    }
+
+    async fn renew_lock(&self, persona_id: &str, app_id: &str, name: &str, token: u64, ttl: Duration) -> Result<Lease> {
+        check_app_id(app_id)?;
+        self.update(persona_id, &lock::lock_app(app_id), name, |current| {
+            let mut state = LockState::from_value(current)?;
+            let lease = state.renew(token, unix_millis(), ttl.as_millis() as u64)?;
+            Ok((Some(state.to_value()?), lease))
+        }).await
+    }
+
+    async fn release_lock(&self, persona_id: &str, app_id: &str, name: &str, token: u64) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update(persona_id, &lock::lock_app(app_id), name, |current| {
+            if current.is_none() {
+                return Err(Error::LockNotHeld);
+            }
+            let mut state = LockState::from_value(current)?;
+            state.release(token, unix_millis())?;
+            Ok((Some(state.to_value()?), ()))
+        }).await
+    }
 }
 
 #[async_trait]
 impl AppEnumeration for MemStore {
     async fn get_personas(&self) -> Result<Vec<String>> {
         let data = self.data.read().unwrap();
-        Ok(data.keys().cloned().collect())
+        // Apps and personas emptied by deletes are kept in memory but not listed
+        Ok(data.iter()
+            .filter(|(_, p)| persona_has_data(p))
+            .map(|(id, _)| id.clone())
+            .collect())
     }
 
     async fn get_apps(&self, persona_id: &str) -> Result<Vec<String>> {
         let data = self.data.read().unwrap();
+        // Sidecar apps (`<app>::meta`, `<app>::history`, ...) are bookkeeping, not apps
         Ok(data.get(persona_id)
-            .map(|p| p.keys().cloned().collect())
+            .map(|p| p.iter()
+                .filter(|(id, a)| keymeta::is_tracked(id) && !a.is_empty())
+                .map(|(id, _)| id.clone())
+                .collect())
             .unwrap_or_default())
     }
+
+    async fn get_keys(&self, persona_id: &str, app_id: &str) -> Result<Vec<String>> {
+        let data = self.data.read().unwrap();
+        let mut keys: Vec<String> = data.get(persona_id)
+            .and_then(|p| p.get(app_id))
+            .map(|a| a.keys().cloned().collect())
+            .unwrap_or_default();
+        keys.sort();
+        Ok(keys)
+    }
 }
 
 #[async_trait]
@@ -146,53 +895,494 @@ impl BatchExporter for MemStore {
         }
         Ok(result)
     }
+
+    async fn query(&self, persona_id: &str, app_id: &str, query: &Query) -> Result<HashMap<String, serde_json::Value>> {
+        let data = self.data.read().unwrap();
+        let app_data = data.get(persona_id)
+            .ok_or(Error::PersonaNotFound)?
+            .get(app_id)
+            .ok_or(Error::AppNotFound)?;
+        Ok(app_data.iter()
+            .filter_map(|(key, val)| query.evaluate(val).map(|v| (key.clone(), v)))
+            .collect())
+    }
+
+    async fn query_app(&self, app_id: &str, query: &Query) -> Result<HashMap<String, HashMap<String, serde_json::Value>>> {
+        let data = self.data.read().unwrap();
+        let mut result = HashMap::new();
+        for (persona_id, apps) in data.iter() {
+            if let Some(app_data) = apps.get(app_id) {
+                let matches: HashMap<String, serde_json::Value> = app_data.iter()
+                    .filter_map(|(key, val)| query.evaluate(val).map(|v| (key.clone(), v)))
+                    .collect();
+                if !matches.is_empty() {
+                    result.insert(persona_id.clone(), matches);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    async fn aggregate(&self, app_id: &str, aggregation: &Aggregation) -> Result<Vec<AggregateGroup>> {
+        let data = self.data.read().unwrap();
+        Ok(aggregation.run(data.values().filter_map(|apps| apps.get(app_id)).flatten()))
+    }
 }
 
 #[async_trait]
 impl GlobalSearcher for MemStore {
     async fn get_global(&self, app_id: &str, key: &str) -> Result<(serde_json::Value, String)> {
+        let (persona_id, val) = self.find_global(app_id, key, &[]).await?
+            .into_iter()
+            .next()
+            .ok_or(Error::KeyNotFound)?;
+        Ok((val, persona_id))
+    }
+
+    async fn find_global(&self, app_id: &str, key: &str, priority: &[&str]) -> Result<Vec<(String, serde_json::Value)>> {
+        let data = self.data.read().unwrap();
+        let mut matches: Vec<(String, serde_json::Value)> = data.iter()
+            .filter_map(|(persona_id, apps)| {
+                apps.get(app_id)
+                    .and_then(|a| a.get(key))
+                    .map(|val| (persona_id.clone(), val.clone()))
+            })
+            .collect();
+
+        // Explicit priorities first, then by ID with the system persona as the fallback
+        matches.sort_by_cached_key(|(persona_id, _)| {
+            let rank = priority.iter().position(|p| p == persona_id).unwrap_or(priority.len());
+            (rank, persona_id == SYSTEM_PERSONA, persona_id.clone())
+        });
+        Ok(matches)
+    }
+
+    async fn find_blind(&self, app_id: &str, digest: &str) -> Result<Vec<(String, String)>> {
+        let index_app = vault::blind_index_app(app_id);
         let data = self.data.read().unwrap();
+        let mut matches = Vec::new();
         for (persona_id, apps) in data.iter() {
-            if let Some(app_data) = apps.get(app_id) {
-                if let Some(val) = app_data.get(key) {
-                    return Ok((val.clone(), persona_id.clone()));
+            if let Some(index) = apps.get(&index_app) {
+                for (key, val) in index {
+                    if val.as_str() == Some(digest) {
+                        matches.push((persona_id.clone(), key.clone()));
+                    }
                 }
             }
         }
-        Err(Error::KeyNotFound)
+        matches.sort();
+        Ok(matches)
     }
 }
 
 #[async_trait]
-impl Orchestrator for MemStore {
-    async fn move_key(&self, src_persona: &str, dst_persona: &str, app_id: &str, key: &str) -> Result<()> {
-        let val = {
+impl VaultStorage for MemStore {
+    async fn vault_set(&self, persona_id: &str, app_id: &str, key: &str, ciphertext: &str, key_id: &str, blind_index: Option<&str>) -> Result<()> {
+        check_app_id(app_id)?;
+        let change = {
             let mut data = self.data.write().unwrap();
-            let src_persona_data = data.get_mut(src_persona).ok_or(Error::PersonaNotFound)?;
-            let src_app_data = src_persona_data.get_mut(app_id).ok_or(Error::AppNotFound)?;
-            src_app_data.remove(key).ok_or(Error::KeyNotFound)?
-        };
+            self.check_writable(persona_id, app_id, key)?;
+            let value = self.run_before_set(persona_id, app_id, key, serde_json::Value::String(ciphertext.to_string()))?;
+            schema::enforce(&data, app_id, &value)?;
 
-        self.set(dst_persona, app_id, key, val).await?;
-        self.persist(src_persona.to_string()).await;
-        
+            let meta_app = vault::meta_app(app_id);
+            let index_app = vault::blind_index_app(app_id);
+            let now = unix_millis();
+            let created_at = data.get(persona_id)
+                .and_then(|p| p.get(&meta_app))
+                .and_then(|m| m.get(key))
+                .and_then(|v| serde_json::from_value::<VaultEntryMeta>(v.clone()).ok())
+                .map(|m| m.created_at)
+                .unwrap_or(now);
+            let meta = VaultEntryMeta { created_at, updated_at: now, key_id: key_id.to_string() };
+
+            let change = self.pending_change(persona_id, app_id, key, Some(&value));
+            self.write_value(&mut data, persona_id, app_id, key, Some(value));
+            self.write_value(&mut data, persona_id, &meta_app, key, Some(serde_json::to_value(meta)?));
+            if let Some(digest) = blind_index {
+                self.write_value(&mut data, persona_id, &index_app, key, Some(serde_json::Value::String(digest.to_string())));
+            }
+            change
+        };
+        self.persist(persona_id.to_string()).await;
+        self.notify(change);
         Ok(())
     }
 }
 
-impl CelerixStore for MemStore {
-    fn app(&self, persona_id: &str, app_id: &str) -> Box<dyn AppScope + '_> {
-        Box::new(MemAppScope {
-            store: self,
-            persona_id: persona_id.to_string(),
-            app_id: app_id.to_string(),
-        })
+#[async_trait]
+impl LayeredConfig for MemStore {
+    async fn set_parents(&self, persona_id: &str, parents: &[&str]) -> Result<()> {
+        let list = (!parents.is_empty()).then(|| serde_json::json!(parents));
+        self.update_config(layers::PARENTS_APP, persona_id, |_| Ok((list, ()))).await
     }
-}
 
-pub struct MemAppScope<'a> {
-    store: &'a MemStore,
-    persona_id: String,
+    async fn get_parents(&self, persona_id: &str) -> Result<Vec<String>> {
+        let data = self.data.read().unwrap();
+        Ok(layers::parents_of(&data, persona_id))
+    }
+
+    async fn get_effective(&self, persona_id: &str, app_id: &str, key: &str, merge: bool) -> Result<EffectiveValue> {
+        let data = self.data.read().unwrap();
+        let found: Vec<(String, &serde_json::Value)> = layers::chain(&data, persona_id)
+            .into_iter()
+            .filter_map(|layer| {
+                let value = data.get(&layer)?.get(app_id)?.get(key)?;
+                Some((layer, value))
+            })
+            .collect();
+        layers::resolve(&found, merge)
+    }
+}
+
+#[async_trait]
+impl IndexManager for MemStore {
+    async fn create_index(&self, app_id: &str, path: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update_config(index::INDEXES_APP, app_id, |current| {
+            let mut paths = current.and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            if !paths.iter().any(|p| p.as_str() == Some(path)) {
+                paths.push(serde_json::json!(path));
+            }
+            Ok((Some(serde_json::Value::Array(paths)), ()))
+        }).await
+    }
+
+    async fn drop_index(&self, app_id: &str, path: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update_config(index::INDEXES_APP, app_id, |current| {
+            let mut paths = current.and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            let before = paths.len();
+            paths.retain(|p| p.as_str() != Some(path));
+            if paths.len() == before {
+                return Err(Error::IndexNotFound);
+            }
+            Ok(((!paths.is_empty()).then_some(serde_json::Value::Array(paths)), ()))
+        }).await
+    }
+
+    async fn list_indexes(&self, app_id: &str) -> Result<Vec<String>> {
+        let data = self.data.read().unwrap();
+        Ok(index::definitions(&data).remove(app_id).unwrap_or_default())
+    }
+
+    async fn find_by_index(&self, app_id: &str, path: &str, value: &serde_json::Value) -> Result<Vec<(String, String)>> {
+        self.indexes.read().unwrap()
+            .lookup(app_id, path, value)
+            .ok_or(Error::IndexNotFound)
+    }
+}
+
+#[async_trait]
+impl TextSearch for MemStore {
+    async fn enable_search(&self, app_id: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update_config(fulltext::FULLTEXT_APP, app_id, |_| Ok((Some(serde_json::Value::Bool(true)), ()))).await
+    }
+
+    async fn disable_search(&self, app_id: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update_config(fulltext::FULLTEXT_APP, app_id, |_| Ok((None, ()))).await
+    }
+
+    async fn search(&self, persona_id: &str, app_id: &str, terms: &str) -> Result<Vec<SearchHit>> {
+        self.text_index.read().unwrap()
+            .search(persona_id, app_id, terms)
+            .ok_or(Error::IndexNotFound)
+    }
+}
+
+#[async_trait]
+impl KeyHistory for MemStore {
+    async fn history(&self, persona_id: &str, app_id: &str, key: &str) -> Result<Vec<HistoryEntry>> {
+        let mut entries = self.read_value(persona_id, &history::history_app(app_id), key, |log| Ok(history::entries(log)))?;
+        entries.reverse();
+        Ok(entries)
+    }
+
+    async fn get_at(&self, persona_id: &str, app_id: &str, key: &str, timestamp: u64) -> Result<serde_json::Value> {
+        self.read_value(persona_id, &history::history_app(app_id), key, |log| {
+            history::state_at(log, timestamp).filter(|e| !e.deleted).map(|e| e.value).ok_or(Error::KeyNotFound)
+        })
+    }
+
+    async fn revert(&self, persona_id: &str, app_id: &str, key: &str, timestamp: u64) -> Result<()> {
+        check_app_id(app_id)?;
+        let target = self.get_at(persona_id, app_id, key, timestamp).await?;
+        self.update(persona_id, app_id, key, |_| Ok((Some(target), ()))).await
+    }
+}
+
+#[async_trait]
+impl TrashBin for MemStore {
+    async fn list_trash(&self, persona_id: &str) -> Result<Vec<TrashEntry>> {
+        let data = self.data.read().unwrap();
+        let retention = self.trash.unwrap_or(Duration::MAX);
+        Ok(data.get(persona_id).map(|p| trash::list(p, retention, unix_millis())).unwrap_or_default())
+    }
+
+    async fn restore(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        let change = {
+            let mut data = self.data.write().unwrap();
+            let retention = self.trash.unwrap_or(Duration::MAX);
+            let trash_app = trash::trash_app(app_id);
+            let persona = data.get(persona_id).ok_or(Error::KeyNotFound)?;
+            let trashed = Trashed::from_value(persona.get(&trash_app).and_then(|t| t.get(key)))
+                .filter(|t| t.expires_at(retention) > unix_millis())
+                .ok_or(Error::KeyNotFound)?;
+            if persona.get(app_id).map(|a| a.contains_key(key)).unwrap_or(false) {
+                return Err(Error::AlreadyExists);
+            }
+            self.check_writable(persona_id, app_id, key)?;
+            let value = self.run_before_set(persona_id, app_id, key, trashed.value)?;
+            schema::enforce(&data, app_id, &value)?;
+            let change = self.pending_change(persona_id, app_id, key, Some(&value));
+            self.write_value(&mut data, persona_id, app_id, key, Some(value));
+            self.write_value(&mut data, persona_id, &trash_app, key, None);
+            change
+        };
+        self.persist(persona_id.to_string()).await;
+        self.notify(change);
+        Ok(())
+    }
+
+    async fn purge(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update(persona_id, &trash::trash_app(app_id), key, |current| {
+            current.ok_or(Error::KeyNotFound)?;
+            Ok((None, ()))
+        }).await
+    }
+}
+
+#[async_trait]
+impl DerivedKeys for MemStore {
+    async fn define_derived(&self, persona_id: &str, app_id: &str, key: &str, derivation: Derivation) -> Result<()> {
+        check_app_id(app_id)?;
+        let def = DerivedKey { target: KeyRef::new(persona_id, app_id, key), derivation };
+        let id = derived::definition_key(&def.target);
+        let value = serde_json::to_value(&def)?;
+        let change = {
+            let mut data = self.data.write().unwrap();
+            // Only a key that is already derived may be redefined; anything else would be overwritten
+            let defined = data.get(SYSTEM_PERSONA).and_then(|p| p.get(derived::DERIVED_APP)).map(|a| a.contains_key(&id)).unwrap_or(false);
+            if !defined && data.get(persona_id).and_then(|p| p.get(app_id)).map(|a| a.contains_key(key)).unwrap_or(false) {
+                return Err(Error::AlreadyExists);
+            }
+            if let Some(computed) = derived::compute(&data, &def.derivation) {
+                schema::enforce(&data, app_id, &computed)?;
+            }
+            let change = self.pending_change(SYSTEM_PERSONA, derived::DERIVED_APP, &id, Some(&value));
+            self.write_value(&mut data, SYSTEM_PERSONA, derived::DERIVED_APP, &id, Some(value));
+            change
+        };
+        self.persist(SYSTEM_PERSONA.to_string()).await;
+        self.notify(change);
+        Ok(())
+    }
+
+    async fn drop_derived(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        let changes = {
+            let mut data = self.data.write().unwrap();
+            let target = KeyRef::new(persona_id, app_id, key);
+            let id = derived::definition_key(&target);
+            if !data.get(SYSTEM_PERSONA).and_then(|p| p.get(derived::DERIVED_APP)).map(|a| a.contains_key(&id)).unwrap_or(false) {
+                return Err(Error::KeyNotFound);
+            }
+            let mut changes = vec![self.pending_change(SYSTEM_PERSONA, derived::DERIVED_APP, &id, None)];
+            self.write_value(&mut data, SYSTEM_PERSONA, derived::DERIVED_APP, &id, None);
+            if data.get(persona_id).and_then(|p| p.get(app_id)).map(|a| a.contains_key(key)).unwrap_or(false) {
+                self.derived_dirty.lock().unwrap().insert(persona_id.to_string());
+                changes.push(self.pending_change(persona_id, app_id, key, None));
+                self.write_value(&mut data, persona_id, app_id, key, None);
+            }
+            changes
+        };
+        self.persist(SYSTEM_PERSONA.to_string()).await;
+        self.notify(changes.into_iter().flatten());
+        Ok(())
+    }
+
+    async fn list_derived(&self) -> Result<Vec<DerivedKey>> {
+        let data = self.data.read().unwrap();
+        Ok(derived::definitions(&data))
+    }
+}
+
+#[async_trait]
+impl SchemaRegistry for MemStore {
+    async fn set_schema(&self, app_id: &str, schema: serde_json::Value) -> Result<()> {
+        check_app_id(app_id)?;
+        if !schema.is_object() && !schema.is_boolean() {
+            return Err(Error::WrongType("schema must be an object or boolean".to_string()));
+        }
+        let problems = schema::unsupported(&schema);
+        if !problems.is_empty() {
+            return Err(Error::ValidationFailed(problems));
+        }
+        self.update_config(schema::SCHEMAS_APP, app_id, |_| Ok((Some(schema), ()))).await
+    }
+
+    async fn get_schema(&self, app_id: &str) -> Result<serde_json::Value> {
+        self.read_value(SYSTEM_PERSONA, schema::SCHEMAS_APP, app_id, |v| v.cloned().ok_or(Error::KeyNotFound))
+    }
+
+    async fn remove_schema(&self, app_id: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        self.update_config(schema::SCHEMAS_APP, app_id, |current| {
+            current.ok_or(Error::KeyNotFound)?;
+            Ok((None, ()))
+        }).await
+    }
+}
+
+#[async_trait]
+impl Orchestrator for MemStore {
+    async fn move_key(&self, src_persona: &str, dst_persona: &str, app_id: &str, key: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        let changes = {
+            let mut data = self.data.write().unwrap();
+            let src_persona_data = data.get(src_persona).ok_or(Error::PersonaNotFound)?;
+            let src_app_data = src_persona_data.get(app_id).ok_or(Error::AppNotFound)?;
+            let val = src_app_data.get(key).cloned().ok_or(Error::KeyNotFound)?;
+            self.check_writable(src_persona, app_id, key)?;
+            self.check_writable(dst_persona, app_id, key)?;
+            // Both sides change under one lock, so a value rejected by the destination is never lost
+            let val = self.run_before_set(dst_persona, app_id, key, val)?;
+            schema::enforce(&data, app_id, &val)?;
+            let changes = [
+                self.pending_change(src_persona, app_id, key, None),
+                self.pending_change(dst_persona, app_id, key, Some(&val)),
+            ];
+            self.write_value(&mut data, src_persona, app_id, key, None);
+            self.write_value(&mut data, dst_persona, app_id, key, Some(val));
+            changes
+        };
+
+        self.persist(dst_persona.to_string()).await;
+        self.persist(src_persona.to_string()).await;
+        self.notify(changes.into_iter().flatten());
+        
+        Ok(())
+    }
+
+    async fn delete_app(&self, persona_id: &str, app_id: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        check_config_app(persona_id, app_id)?;
+        let changes = {
+            let mut data = self.data.write().unwrap();
+            let persona = data.get_mut(persona_id).ok_or(Error::PersonaNotFound)?;
+            if !app_has_data(persona, app_id) {
+                return Err(Error::AppNotFound);
+            }
+            let changes = self.pending_app_changes(persona_id, persona, Some(app_id), true);
+            persona.retain(|name, _| !belongs_to_app(name, app_id));
+            self.rebuild_indexes(&mut data);
+            changes
+        };
+        self.persist(persona_id.to_string()).await;
+        self.notify(changes);
+        Ok(())
+    }
+
+    async fn delete_persona(&self, persona_id: &str) -> Result<()> {
+        let changes = {
+            let mut data = self.data.write().unwrap();
+            let persona = data.remove(persona_id).ok_or(Error::PersonaNotFound)?;
+            self.rebuild_indexes(&mut data);
+            self.pending_app_changes(persona_id, &persona, None, true)
+        };
+        self.persist(persona_id.to_string()).await;
+        self.notify(changes);
+        Ok(())
+    }
+
+    async fn rename_persona(&self, src_persona: &str, dst_persona: &str) -> Result<()> {
+        let changes = {
+            let mut data = self.data.write().unwrap();
+            if !data.contains_key(src_persona) {
+                return Err(Error::PersonaNotFound);
+            }
+            if data.get(dst_persona).map(persona_has_data).unwrap_or(false) {
+                return Err(Error::AlreadyExists);
+            }
+            let persona = data.remove(src_persona).unwrap_or_default();
+            let mut changes = self.pending_app_changes(src_persona, &persona, None, true);
+            changes.extend(self.pending_app_changes(dst_persona, &persona, None, false));
+            data.insert(dst_persona.to_string(), persona);
+            self.rebuild_indexes(&mut data);
+            changes
+        };
+        self.persist(dst_persona.to_string()).await;
+        self.persist(src_persona.to_string()).await;
+        self.notify(changes);
+        Ok(())
+    }
+
+    async fn copy_persona(&self, src_persona: &str, dst_persona: &str) -> Result<()> {
+        let changes = {
+            let mut data = self.data.write().unwrap();
+            let persona = data.get(src_persona).cloned().ok_or(Error::PersonaNotFound)?;
+            if data.get(dst_persona).map(persona_has_data).unwrap_or(false) {
+                return Err(Error::AlreadyExists);
+            }
+            let changes = self.pending_app_changes(dst_persona, &persona, None, false);
+            data.insert(dst_persona.to_string(), persona);
+            self.rebuild_indexes(&mut data);
+            changes
+        };
+        self.persist(dst_persona.to_string()).await;
+        self.notify(changes);
+        Ok(())
+    }
+
+    async fn move_app(&self, src_persona: &str, dst_persona: &str, app_id: &str) -> Result<()> {
+        check_app_id(app_id)?;
+        check_config_app(src_persona, app_id)?;
+        check_config_app(dst_persona, app_id)?;
+        if src_persona == dst_persona {
+            return Err(Error::AlreadyExists);
+        }
+        let changes = {
+            let mut data = self.data.write().unwrap();
+            let src = data.get(src_persona).ok_or(Error::PersonaNotFound)?;
+            if !app_has_data(src, app_id) {
+                return Err(Error::AppNotFound);
+            }
+            if data.get(dst_persona).map(|p| app_has_data(p, app_id)).unwrap_or(false) {
+                return Err(Error::AlreadyExists);
+            }
+
+            let src = data.get_mut(src_persona).unwrap();
+            let mut changes = self.pending_app_changes(src_persona, src, Some(app_id), true);
+            changes.extend(self.pending_app_changes(dst_persona, src, Some(app_id), false));
+            let names: Vec<String> = src.keys().filter(|name| belongs_to_app(name, app_id)).cloned().collect();
+            let moved: Vec<_> = names.into_iter().filter_map(|name| src.remove(&name).map(|app| (name, app))).collect();
+            data.entry(dst_persona.to_string()).or_default().extend(moved);
+            self.rebuild_indexes(&mut data);
+            changes
+        };
+        self.persist(dst_persona.to_string()).await;
+        self.persist(src_persona.to_string()).await;
+        self.notify(changes);
+        Ok(())
+    }
+}
+
+impl CelerixStore for MemStore {
+    fn app(&self, persona_id: &str, app_id: &str) -> Box<dyn AppScope + '_> {
+        Box::new(MemAppScope {
+            store: self,
+            persona_id: persona_id.to_string(),
+            app_id: app_id.to_string(),
+        })
+    }
+}
+
+pub struct MemAppScope<'a> {
+    store: &'a MemStore,
+    persona_id: String,
     app_id: String,
 }
 
@@ -202,38 +1392,240 @@ impl<'a> AppScope for MemAppScope<'a> {
         self.store.get(&self.persona_id, &self.app_id, key).await
     }
 
+    async fn get_path(&self, key: &str, path: &str) -> Result<serde_json::Value> {
+        self.store.get_path(&self.persona_id, &self.app_id, key, path).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<serde_json::Value>>> {
+        let keys: Vec<_> = keys.iter().map(|k| (self.persona_id.as_str(), self.app_id.as_str(), *k)).collect();
+        self.store.get_many(&keys).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        self.store.exists(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn stat(&self, key: &str) -> Result<KeyStat> {
+        self.store.stat(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.store.get_keys(&self.persona_id, &self.app_id).await
+    }
+
     async fn set(&self, key: &str, value: serde_json::Value) -> Result<()> {
         self.store.set(&self.persona_id, &self.app_id, key, value).await
     }
 
+    async fn set_many(&self, entries: HashMap<String, serde_json::Value>) -> Result<()> {
+        let entries = entries.iter()
+            .map(|(k, v)| (self.persona_id.as_str(), self.app_id.as_str(), k.as_str(), v.clone()))
+            .collect();
+        self.store.set_many(entries).await
+    }
+
     async fn delete(&self, key: &str) -> Result<()> {
         self.store.delete(&self.persona_id, &self.app_id, key).await
     }
 
+    async fn incr(&self, key: &str, delta: i64) -> Result<i64> {
+        self.store.incr(&self.persona_id, &self.app_id, key, delta).await
+    }
+
+    async fn patch(&self, key: &str, patch: Patch) -> Result<serde_json::Value> {
+        self.store.patch(&self.persona_id, &self.app_id, key, patch).await
+    }
+
+    async fn list_push(&self, key: &str, end: ListEnd, value: serde_json::Value) -> Result<usize> {
+        self.store.list_push(&self.persona_id, &self.app_id, key, end, value).await
+    }
+
+    async fn list_pop(&self, key: &str, end: ListEnd) -> Result<Option<serde_json::Value>> {
+        self.store.list_pop(&self.persona_id, &self.app_id, key, end).await
+    }
+
+    async fn list_range(&self, key: &str, start: i64, stop: i64) -> Result<Vec<serde_json::Value>> {
+        self.store.list_range(&self.persona_id, &self.app_id, key, start, stop).await
+    }
+
+    async fn list_len(&self, key: &str) -> Result<usize> {
+        self.store.list_len(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn set_add(&self, key: &str, member: serde_json::Value) -> Result<bool> {
+        self.store.set_add(&self.persona_id, &self.app_id, key, member).await
+    }
+
+    async fn set_remove(&self, key: &str, member: &serde_json::Value) -> Result<bool> {
+        self.store.set_remove(&self.persona_id, &self.app_id, key, member).await
+    }
+
+    async fn set_members(&self, key: &str) -> Result<Vec<serde_json::Value>> {
+        self.store.set_members(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn set_contains(&self, key: &str, member: &serde_json::Value) -> Result<bool> {
+        self.store.set_contains(&self.persona_id, &self.app_id, key, member).await
+    }
+
     fn vault(&self, master_key: &[u8]) -> Box<dyn VaultScope + '_> {
         Box::new(MemVaultScope {
             app: self,
-            master_key: master_key.to_vec(),
+            master_key: SecretBytes::new(master_key.to_vec()),
         })
     }
 }
 
 pub struct MemVaultScope<'a> {
     app: &'a MemAppScope<'a>,
-    master_key: Vec<u8>,
+    master_key: SecretBytes,
+}
+
+impl<'a> MemVaultScope<'a> {
+    /// Encrypts and stores an entry, with its blind index if `indexed` is set.
+    async fn write_entry(&self, key: &str, plaintext: &str, indexed: bool) -> Result<()> {
+        let cipher_hex = vault::encrypt(plaintext, self.master_key.expose())?;
+        let key_id = vault::key_id(self.master_key.expose())?;
+        let digest = if indexed { Some(vault::blind_index(plaintext, self.master_key.expose())?) } else { None };
+        self.app.store.vault_set(&self.app.persona_id, &self.app.app_id, key, &cipher_hex, &key_id, digest.as_deref()).await?;
+        self.refresh_grants(key, plaintext).await
+    }
+
+    /// Records the grants of `key` given to other personas, keyed by recipient with the wrapped recipient key.
+    async fn granted(&self, key: &str) -> Result<serde_json::Map<String, serde_json::Value>> {
+        match self.app.store.get(&self.app.persona_id, &vault::granted_app(&self.app.app_id), key).await {
+            Ok(serde_json::Value::Object(granted)) => Ok(granted),
+            Ok(_) | Err(Error::PersonaNotFound) | Err(Error::AppNotFound) | Err(Error::KeyNotFound) => Ok(serde_json::Map::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores `plaintext` as a grant of `key` to `dst_persona`, encrypted under its recipient key.
+    async fn write_grant(&self, key: &str, dst_persona: &str, plaintext: &str, recipient_key: &[u8]) -> Result<()> {
+        let cipher_hex = vault::encrypt(plaintext, recipient_key)?;
+        let grant_key = vault::grant_key(&self.app.persona_id, key);
+        self.app.store.set(dst_persona, &vault::grants_app(&self.app.app_id), &grant_key, serde_json::Value::String(cipher_hex)).await
+    }
+
+    /// Re-encrypts the grants of `key` after its secret changed.
+    async fn refresh_grants(&self, key: &str, plaintext: &str) -> Result<()> {
+        for (dst_persona, wrapped) in self.granted(key).await? {
+            let wrapped = wrapped.as_str().ok_or_else(|| Error::Internal("Vault data is not a string".to_string()))?;
+            let recipient_key = vault::unwrap_key(wrapped, self.master_key.expose())?;
+            self.write_grant(key, &dst_persona, plaintext, recipient_key.expose()).await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl<'a> VaultScope for MemVaultScope<'a> {
-    async fn get(&self, key: &str) -> Result<String> {
+    async fn get(&self, key: &str) -> Result<SecretString> {
         let val = self.app.get(key).await?;
         let cipher_hex = val.as_str().ok_or_else(|| Error::Internal("Vault data is not a string".to_string()))?;
-        vault::decrypt(cipher_hex, &self.master_key)
+        vault::decrypt(cipher_hex, self.master_key.expose())
     }
 
     async fn set(&self, key: &str, plaintext: &str) -> Result<()> {
-        let cipher_hex = vault::encrypt(plaintext, &self.master_key)?;
-        self.app.set(key, serde_json::Value::String(cipher_hex)).await
+        self.write_entry(key, plaintext, false).await
+    }
+
+    async fn set_indexed(&self, key: &str, plaintext: &str) -> Result<()> {
+        self.write_entry(key, plaintext, true).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        // The store drops the entry's metadata and blind index along with it
+        self.app.delete(key).await?;
+        for dst_persona in self.granted(key).await?.keys() {
+            self.revoke(key, dst_persona).await?;
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let entries = match self.app.store.get_app_store(&self.app.persona_id, &self.app.app_id).await {
+            Ok(entries) => entries,
+            Err(Error::PersonaNotFound) | Err(Error::AppNotFound) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut keys: Vec<String> = entries.into_iter()
+            .filter(|(_, val)| vault::is_ciphertext(val))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self.app.get(key).await {
+            Ok(val) => Ok(vault::is_ciphertext(&val)),
+            Err(Error::PersonaNotFound) | Err(Error::AppNotFound) | Err(Error::KeyNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn metadata(&self, key: &str) -> Result<VaultEntryMeta> {
+        let val = self.app.get(key).await?;
+        let cipher_hex = val.as_str().filter(|_| vault::is_ciphertext(&val)).ok_or(Error::KeyNotFound)?;
+        match self.app.store.get(&self.app.persona_id, &vault::meta_app(&self.app.app_id), key).await {
+            Ok(meta) => Ok(serde_json::from_value(meta)?),
+            Err(Error::AppNotFound) | Err(Error::KeyNotFound) => {
+                VaultEntryMeta::unrecorded(cipher_hex, &self.app.stat(key).await?, self.master_key.expose())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn lookup(&self, plaintext: &str) -> Result<Vec<String>> {
+        let digest = vault::blind_index(plaintext, self.master_key.expose())?;
+        let index = match self.app.store.get_app_store(&self.app.persona_id, &vault::blind_index_app(&self.app.app_id)).await {
+            Ok(index) => index,
+            Err(Error::PersonaNotFound) | Err(Error::AppNotFound) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut keys: Vec<String> = index.into_iter()
+            .filter(|(_, val)| val.as_str() == Some(digest.as_str()))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn recipient_key(&self, src_persona: &str) -> Result<SecretBytes> {
+        vault::recipient_key(self.master_key.expose(), &self.app.app_id, src_persona, &self.app.persona_id)
+    }
+
+    async fn grant(&self, key: &str, dst_persona: &str, recipient_key: &[u8]) -> Result<()> {
+        let plaintext = self.get(key).await?;
+        self.write_grant(key, dst_persona, plaintext.expose(), recipient_key).await?;
+        let wrapped = vault::wrap_key(recipient_key, self.master_key.expose())?;
+        let record = Patch::Merge(serde_json::json!({ dst_persona: wrapped }));
+        self.app.store.patch(&self.app.persona_id, &vault::granted_app(&self.app.app_id), key, record).await?;
+        Ok(())
+    }
+
+    async fn revoke(&self, key: &str, dst_persona: &str) -> Result<()> {
+        let grant_key = vault::grant_key(&self.app.persona_id, key);
+        self.app.store.delete(dst_persona, &vault::grants_app(&self.app.app_id), &grant_key).await?;
+        let mut granted = self.granted(key).await?;
+        if granted.remove(dst_persona).is_some() {
+            let granted_app = vault::granted_app(&self.app.app_id);
+            if granted.is_empty() {
+                self.app.store.delete(&self.app.persona_id, &granted_app, key).await?;
+            } else {
+                self.app.store.set(&self.app.persona_id, &granted_app, key, serde_json::Value::Object(granted)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_grant(&self, src_persona: &str, key: &str) -> Result<SecretString> {
+        let grant_key = vault::grant_key(src_persona, key);
+        let val = self.app.store.get(&self.app.persona_id, &vault::grants_app(&self.app.app_id), &grant_key).await?;
+        let cipher_hex = val.as_str().ok_or_else(|| Error::Internal("Vault data is not a string".to_string()))?;
+        let recipient_key = self.recipient_key(src_persona)?;
+        vault::decrypt(cipher_hex, recipient_key.expose())
     }
 }
 
@@ -261,6 +1653,694 @@ mod tests {
         assert!(matches!(res, Err(Error::KeyNotFound)));
     }
 
+    #[tokio::test]
+    async fn test_get_many_set_many() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set_many(vec![
+            ("p1", "profile", "name", json!("bob")),
+            ("p1", "profile", "age", json!(42)),
+            ("p2", "settings", "theme", json!("dark")),
+        ]).await.unwrap();
+
+        let values = store.get_many(&[("p1", "profile", "age"), ("p1", "profile", "missing"), ("p2", "settings", "theme")]).await.unwrap();
+        assert_eq!(values, vec![Some(json!(42)), None, Some(json!("dark"))]);
+        assert_eq!(store.get_many(&[("p3", "profile", "name")]).await.unwrap(), vec![None]);
+
+        let scope = store.app("p1", "profile");
+        scope.set_many(HashMap::from([("email".to_string(), json!("bob@example.com"))])).await.unwrap();
+        assert_eq!(scope.get_many(&["name", "email"]).await.unwrap(), vec![Some(json!("bob")), Some(json!("bob@example.com"))]);
+    }
+
+    #[tokio::test]
+    async fn test_exists_stat_keys() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set("p1", "app1", "b", json!("x")).await.unwrap();
+        store.set("p1", "app1", "a", json!({"n": 1})).await.unwrap();
+        store.set("p1", "app1", "a", json!({"n": 2})).await.unwrap();
+
+        assert!(store.exists("p1", "app1", "a").await.unwrap());
+        assert!(!store.exists("p1", "other", "a").await.unwrap());
+        assert_eq!(store.get_keys("p1", "app1").await.unwrap(), vec!["a", "b"]);
+        // The `app1::meta` sidecar holding versions is not listed as an app
+        assert_eq!(store.get_apps("p1").await.unwrap(), vec!["app1"]);
+
+        let stat = store.stat("p1", "app1", "a").await.unwrap();
+        assert_eq!(stat.size_bytes, 7);
+        assert_eq!(stat.version, 2);
+        assert!(stat.created_at.unwrap() <= stat.updated_at.unwrap());
+
+        store.delete("p1", "app1", "a").await.unwrap();
+        store.set("p1", "app1", "a", json!(1)).await.unwrap();
+        assert_eq!(store.stat("p1", "app1", "a").await.unwrap().version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_app_ids_reserve_sidecar_separator() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.enqueue("p1", "jobs", "q", json!(1)).await.unwrap();
+        store.set("p1", "cfg", "k", json!(1)).await.unwrap();
+
+        // Sidecars cannot be written or created by callers, so queue state stays intact
+        assert!(matches!(store.set("p1", "jobs::queue", "q", json!([])).await, Err(Error::InvalidAppId(_))));
+        assert!(matches!(store.set("p1", "cfg::v2", "k", json!(2)).await, Err(Error::InvalidAppId(_))));
+        assert!(matches!(store.set_many(vec![("p1", "cfg", "a", json!(1)), ("p1", "cfg::v2", "b", json!(2))]).await, Err(Error::InvalidAppId(_))));
+        assert!(matches!(store.enqueue("p1", "cfg::v2", "q", json!(1)).await, Err(Error::InvalidAppId(_))));
+        assert!(matches!(store.acquire_lock("p1", "cfg::v2", "l", Duration::from_secs(1)).await, Err(Error::InvalidAppId(_))));
+        assert!(matches!(store.delete_app("p1", "jobs::queue").await, Err(Error::InvalidAppId(_))));
+        assert!(matches!(store.move_app("p1", "p2", "cfg::v2").await, Err(Error::InvalidAppId(_))));
+        assert!(matches!(store.get("p1", "cfg", "a").await, Err(Error::KeyNotFound)));
+        assert!(store.dequeue("p1", "jobs", "q", Duration::from_secs(30), Duration::ZERO).await.unwrap().is_some());
+
+        // Vault scopes keep their grant records through the regular API
+        store.set("p2", "secrets::grants", "1:p/k", json!("00")).await.unwrap();
+        assert!(matches!(store.set("p2", "a::b::grants", "k", json!("00")).await, Err(Error::InvalidAppId(_))));
+    }
+
+    #[tokio::test]
+    async fn test_config_apps_reject_direct_writes() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set_schema("users", json!({"type": "object"})).await.unwrap();
+
+        assert!(matches!(store.set(SYSTEM_PERSONA, schema::SCHEMAS_APP, "users", json!({"pattern": "a"})).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.delete(SYSTEM_PERSONA, schema::SCHEMAS_APP, "users").await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.set(SYSTEM_PERSONA, index::INDEXES_APP, "users", json!(1)).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.set_many(vec![(SYSTEM_PERSONA, fulltext::FULLTEXT_APP, "users", json!("yes"))]).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.set(SYSTEM_PERSONA, layers::PARENTS_APP, "p1", json!("p2")).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.delete_app(SYSTEM_PERSONA, schema::SCHEMAS_APP).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.move_app("p1", SYSTEM_PERSONA, index::INDEXES_APP).await, Err(Error::ReadOnly(_))));
+        assert_eq!(store.get_schema("users").await.unwrap(), json!({"type": "object"}));
+
+        // The managed methods still write them, and other _system apps stay writable
+        store.create_index("users", "/name").await.unwrap();
+        store.enable_search("users").await.unwrap();
+        store.set_parents("p1", &["p2"]).await.unwrap();
+        store.remove_schema("users").await.unwrap();
+        store.set(SYSTEM_PERSONA, "config", "theme", json!("dark")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_incr() {
+        let store = MemStore::new(HashMap::new(), None);
+        assert_eq!(store.incr("p1", "app1", "counter", 1).await.unwrap(), 1);
+        assert_eq!(store.incr("p1", "app1", "counter", 5).await.unwrap(), 6);
+        assert_eq!(store.incr("p1", "app1", "counter", -10).await.unwrap(), -4);
+        assert_eq!(store.get("p1", "app1", "counter").await.unwrap(), json!(-4));
+
+        store.set("p1", "app1", "name", json!("bob")).await.unwrap();
+        assert!(matches!(store.incr("p1", "app1", "name", 1).await, Err(Error::WrongType(_))));
+        store.set("p1", "app1", "ratio", json!(1.5)).await.unwrap();
+        assert!(matches!(store.incr("p1", "app1", "ratio", 1).await, Err(Error::WrongType(_))));
+        store.set("p1", "app1", "max", json!(i64::MAX)).await.unwrap();
+        assert!(matches!(store.incr("p1", "app1", "max", 1).await, Err(Error::OutOfRange(_))));
+        assert_eq!(store.get("p1", "app1", "max").await.unwrap(), json!(i64::MAX));
+        store.set("p1", "app1", "huge", json!(u64::MAX)).await.unwrap();
+        assert!(matches!(store.incr("p1", "app1", "huge", -1).await, Err(Error::OutOfRange(_))));
+        assert_eq!(store.get("p1", "app1", "huge").await.unwrap(), json!(u64::MAX));
+    }
+
+    #[tokio::test]
+    async fn test_get_path() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set("p1", "app1", "doc", json!({"a": {"b": [10, {"c": "deep"}]}})).await.unwrap();
+
+        assert_eq!(store.get_path("p1", "app1", "doc", "/a/b/0").await.unwrap(), json!(10));
+        assert_eq!(store.get_path("p1", "app1", "doc", "/a/b/1").await.unwrap(), json!({"c": "deep"}));
+        assert_eq!(store.get_path("p1", "app1", "doc", "").await.unwrap(), store.get("p1", "app1", "doc").await.unwrap());
+        assert!(matches!(store.get_path("p1", "app1", "doc", "/a/x").await, Err(Error::PathNotFound)));
+        assert!(matches!(store.get_path("p1", "app1", "nope", "/a").await, Err(Error::KeyNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_patch() {
+        let store = MemStore::new(HashMap::new(), None);
+        let scope = store.app("p1", "app1");
+        scope.set("key2", json!({"sub": "val", "keep": 1})).await.unwrap();
+
+        let merged = scope.patch("key2", Patch::Merge(json!({"sub": "new", "keep": null, "add": [1]}))).await.unwrap();
+        assert_eq!(merged, json!({"sub": "new", "add": [1]}));
+
+        let patched = scope.patch("key2", Patch::Json(json!([
+            {"op": "test", "path": "/sub", "value": "new"},
+            {"op": "add", "path": "/add/-", "value": 2}
+        ]))).await.unwrap();
+        assert_eq!(patched, json!({"sub": "new", "add": [1, 2]}));
+
+        // A failing `test` leaves the value untouched
+        let res = scope.patch("key2", Patch::Json(json!([
+            {"op": "replace", "path": "/sub", "value": "lost"},
+            {"op": "test", "path": "/sub", "value": "other"}
+        ]))).await;
+        assert!(matches!(res, Err(Error::PatchFailed(_))));
+        assert_eq!(scope.get("key2").await.unwrap(), patched);
+
+        // Missing keys start from null
+        assert_eq!(scope.patch("fresh", Patch::Merge(json!({"a": 1}))).await.unwrap(), json!({"a": 1}));
+    }
+
+    #[tokio::test]
+    async fn test_list_operations() {
+        let store = MemStore::new(HashMap::new(), None);
+        let jobs = store.app("p1", "work");
+
+        assert_eq!(jobs.list_len("jobs").await.unwrap(), 0);
+        assert_eq!(jobs.list_pop("jobs", ListEnd::Front).await.unwrap(), None);
+        assert_eq!(jobs.list_push("jobs", ListEnd::Back, json!("b")).await.unwrap(), 1);
+        assert_eq!(jobs.list_push("jobs", ListEnd::Back, json!("c")).await.unwrap(), 2);
+        assert_eq!(jobs.list_push("jobs", ListEnd::Front, json!("a")).await.unwrap(), 3);
+
+        assert_eq!(jobs.list_range("jobs", 0, -1).await.unwrap(), vec![json!("a"), json!("b"), json!("c")]);
+        assert_eq!(jobs.list_range("jobs", -2, 10).await.unwrap(), vec![json!("b"), json!("c")]);
+        assert!(jobs.list_range("jobs", 5, 10).await.unwrap().is_empty());
+
+        assert_eq!(jobs.list_pop("jobs", ListEnd::Front).await.unwrap(), Some(json!("a")));
+        assert_eq!(jobs.list_pop("jobs", ListEnd::Back).await.unwrap(), Some(json!("c")));
+        assert_eq!(jobs.get("jobs").await.unwrap(), json!(["b"]));
+
+        jobs.set("scalar", json!(1)).await.unwrap();
+        assert!(matches!(jobs.list_push("scalar", ListEnd::Back, json!(2)).await, Err(Error::WrongType(_))));
+    }
+
+    #[tokio::test]
+    async fn test_set_operations() {
+        let store = MemStore::new(HashMap::new(), None);
+        let team = store.app("p1", "acl");
+
+        assert!(team.set_add("members", json!("alice")).await.unwrap());
+        assert!(team.set_add("members", json!("bob")).await.unwrap());
+        assert!(!team.set_add("members", json!("alice")).await.unwrap());
+        assert_eq!(team.set_members("members").await.unwrap(), vec![json!("alice"), json!("bob")]);
+
+        assert!(team.set_contains("members", &json!("bob")).await.unwrap());
+        assert!(team.set_remove("members", &json!("bob")).await.unwrap());
+        assert!(!team.set_remove("members", &json!("bob")).await.unwrap());
+        assert!(!team.set_contains("members", &json!("bob")).await.unwrap());
+        assert!(!team.set_contains("nobody", &json!("bob")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_queue_ack_nack_and_dead_letter() {
+        let store = MemStore::new(HashMap::new(), None).with_queue_max_attempts(2);
+        let visibility = Duration::from_secs(30);
+
+        let id = store.enqueue("p1", "jobs", "email", json!({"to": "a@b.c"})).await.unwrap();
+        store.enqueue("p1", "jobs", "email", json!({"to": "d@e.f"})).await.unwrap();
+
+        let msg = store.dequeue("p1", "jobs", "email", visibility, Duration::ZERO).await.unwrap().unwrap();
+        assert_eq!((msg.id, msg.attempts), (id, 1));
+        store.nack("p1", "jobs", "email", &msg.receipt).await.unwrap();
+
+        // Nacked message is redelivered first; the second delivery exhausts its attempts
+        let again = store.dequeue("p1", "jobs", "email", visibility, Duration::ZERO).await.unwrap().unwrap();
+        assert_eq!((again.id, again.attempts), (id, 2));
+        assert!(matches!(store.ack("p1", "jobs", "email", &msg.receipt).await, Err(Error::MessageNotFound)));
+        store.nack("p1", "jobs", "email", &again.receipt).await.unwrap();
+        assert_eq!(store.dead_letters("p1", "jobs", "email").await.unwrap()[0].id, id);
+
+        let other = store.dequeue("p1", "jobs", "email", visibility, Duration::ZERO).await.unwrap().unwrap();
+        store.ack("p1", "jobs", "email", &other.receipt).await.unwrap();
+        assert!(store.dequeue("p1", "jobs", "email", visibility, Duration::ZERO).await.unwrap().is_none());
+        assert!(matches!(store.ack("p1", "jobs", "email", &other.receipt).await, Err(Error::MessageNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_queue_blocking_dequeue_and_visibility_timeout() {
+        let store = Arc::new(MemStore::new(HashMap::new(), None));
+
+        let consumer = {
+            let store = store.clone();
+            tokio::spawn(async move {
+                store.dequeue("p1", "jobs", "q", Duration::from_millis(50), Duration::from_secs(5)).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        store.enqueue("p1", "jobs", "q", json!(1)).await.unwrap();
+        let msg = consumer.await.unwrap().unwrap().unwrap();
+        assert_eq!(msg.body, json!(1));
+
+        // Not acknowledged: the message reappears once its visibility timeout expires
+        let redelivered = store.dequeue("p1", "jobs", "q", Duration::from_millis(50), Duration::from_secs(5)).await.unwrap().unwrap();
+        assert_eq!((redelivered.id, redelivered.attempts), (msg.id, 2));
+    }
+
+    #[tokio::test]
+    async fn test_locks_expire_and_fence() {
+        let store = MemStore::new(HashMap::new(), None);
+        let ttl = Duration::from_millis(50);
+
+        let lease = store.acquire_lock("p1", "svc", "leader", ttl).await.unwrap().unwrap();
+        assert!(store.acquire_lock("p1", "svc", "leader", ttl).await.unwrap().is_none());
+        store.renew_lock("p1", "svc", "leader", lease.token, ttl).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let next = store.acquire_lock("p1", "svc", "leader", ttl).await.unwrap().unwrap();
+        assert!(next.token > lease.token);
+        assert!(matches!(store.renew_lock("p1", "svc", "leader", lease.token, ttl).await, Err(Error::LockNotHeld)));
+
+        store.release_lock("p1", "svc", "leader", next.token).await.unwrap();
+        assert!(store.acquire_lock("p1", "svc", "leader", ttl).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_find_global_order() {
+        let store = MemStore::new(HashMap::new(), None);
+        for persona_id in ["bob", SYSTEM_PERSONA, "alice", "carol"] {
+            store.set(persona_id, "settings", "theme", json!(persona_id)).await.unwrap();
+        }
+        store.set("dave", "settings", "other", json!(1)).await.unwrap();
+
+        let order = |matches: Vec<(String, serde_json::Value)>| matches.into_iter().map(|(p, _)| p).collect::<Vec<_>>();
+        let all = store.find_global("settings", "theme", &[]).await.unwrap();
+        assert_eq!(order(all), vec!["alice", "bob", "carol", SYSTEM_PERSONA]);
+
+        let prioritized = store.find_global("settings", "theme", &["carol", "nobody"]).await.unwrap();
+        assert_eq!(prioritized[0], ("carol".to_string(), json!("carol")));
+        assert_eq!(order(prioritized), vec!["carol", "alice", "bob", SYSTEM_PERSONA]);
+
+        assert_eq!(store.get_global("settings", "theme").await.unwrap().1, "alice");
+    }
+
+    #[tokio::test]
+    async fn test_get_effective_through_parents() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set(SYSTEM_PERSONA, "ui", "prefs", json!({"theme": "light", "lang": "en"})).await.unwrap();
+        store.set("acme", "ui", "prefs", json!({"theme": "dark"})).await.unwrap();
+        store.set("alice", "ui", "prefs", json!({"lang": "de"})).await.unwrap();
+        store.set_parents("alice", &["acme"]).await.unwrap();
+        assert_eq!(store.get_parents("alice").await.unwrap(), vec!["acme"]);
+
+        let top = store.get_effective("alice", "ui", "prefs", false).await.unwrap();
+        assert_eq!(top.value, json!({"lang": "de"}));
+
+        let merged = store.get_effective("alice", "ui", "prefs", true).await.unwrap();
+        assert_eq!(merged.value, json!({"theme": "dark", "lang": "de"}));
+        assert_eq!(merged.sources["/theme"], "acme");
+        assert_eq!(merged.sources["/lang"], "alice");
+
+        // Without parents, the system persona is still the fallback
+        let bob = store.get_effective("bob", "ui", "prefs", false).await.unwrap();
+        assert_eq!(bob.sources[""], SYSTEM_PERSONA);
+        assert!(matches!(store.get_effective("bob", "ui", "missing", true).await, Err(Error::KeyNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_query_predicates_and_projection() {
+        use crate::Predicate;
+
+        let store = MemStore::new(HashMap::new(), None);
+        store.set("p1", "users", "u1", json!({"name": "Ann", "age": 31, "address": {"city": "Oslo", "zip": "0150"}})).await.unwrap();
+        store.set("p1", "users", "u2", json!({"name": "Bob", "age": 17.0, "address": {"city": "Bergen"}})).await.unwrap();
+        store.set("p1", "users", "u3", json!({"name": "Cy", "banned": true})).await.unwrap();
+        store.set("p2", "users", "u4", json!({"name": "Di", "age": 45, "address": {"city": "Oslo"}})).await.unwrap();
+
+        let adults = Query {
+            filter: Some(Predicate::And(vec![
+                Predicate::Gte { path: "/age".to_string(), value: json!(18) },
+                Predicate::Not(Box::new(Predicate::Exists { path: "/banned".to_string() })),
+            ])),
+            fields: vec!["/name".to_string(), "/address/city".to_string()],
+        };
+        let found = store.query("p1", "users", &adults).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found["u1"], json!({"name": "Ann", "address": {"city": "Oslo"}}));
+
+        // Overlapping fields keep the wider value, and array indexes stay in arrays
+        let tagged = json!({"tags": ["a", "b", "c"], "pairs": [[1, 2], [3, 4]]});
+        let overlapping = Query { filter: None, fields: vec!["/tags".to_string(), "/tags/0".to_string()] };
+        assert_eq!(overlapping.evaluate(&tagged), Some(json!({"tags": ["a", "b", "c"]})));
+        let reversed = Query { filter: None, fields: vec!["/tags/1".to_string(), "/tags".to_string()] };
+        assert_eq!(reversed.evaluate(&tagged), Some(json!({"tags": ["a", "b", "c"]})));
+        let indexed = Query { filter: None, fields: vec!["/tags/1".to_string(), "/pairs/1/0".to_string()] };
+        assert_eq!(indexed.evaluate(&tagged), Some(json!({"tags": [null, "b"], "pairs": [null, [3]]})));
+
+        let by_city = Query {
+            filter: Some(Predicate::Or(vec![
+                Predicate::In { path: "/address/city".to_string(), values: vec![json!("Oslo")] },
+                Predicate::Eq { path: "/age".to_string(), value: json!(17) },
+            ])),
+            fields: Vec::new(),
+        };
+        let across = store.query_app("users", &by_city).await.unwrap();
+        let mut keys: Vec<&String> = across.values().flat_map(|m| m.keys()).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["u1", "u2", "u4"]);
+        assert!(matches!(store.query("p3", "users", &by_city).await, Err(Error::PersonaNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_secondary_index_maintenance() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Arc::new(Persistence::new(dir.path()).unwrap());
+        let store = MemStore::new(HashMap::new(), Some(persistence.clone()));
+        store.set("p1", "users", "u1", json!({"email": "ann@x.io"})).await.unwrap();
+        store.create_index("users", "/email").await.unwrap();
+
+        let pair = |p: &str, k: &str| (p.to_string(), k.to_string());
+        assert_eq!(store.find_by_index("users", "/email", &json!("ann@x.io")).await.unwrap(), vec![pair("p1", "u1")]);
+
+        store.set("p2", "users", "u2", json!({"email": "bob@x.io"})).await.unwrap();
+        store.move_key("p2", "p3", "users", "u2").await.unwrap();
+        assert_eq!(store.find_by_index("users", "/email", &json!("bob@x.io")).await.unwrap(), vec![pair("p3", "u2")]);
+
+        store.patch("p1", "users", "u1", Patch::Merge(json!({"email": "ann@y.io"}))).await.unwrap();
+        assert!(store.find_by_index("users", "/email", &json!("ann@x.io")).await.unwrap().is_empty());
+        store.delete("p3", "users", "u2").await.unwrap();
+        assert!(store.find_by_index("users", "/email", &json!("bob@x.io")).await.unwrap().is_empty());
+
+        // Definitions are persisted and the entries rebuilt on load
+        store.wait().await;
+        let reloaded = MemStore::new(persistence.load_all().unwrap(), None);
+        assert_eq!(reloaded.list_indexes("users").await.unwrap(), vec!["/email"]);
+        assert_eq!(reloaded.find_by_index("users", "/email", &json!("ann@y.io")).await.unwrap(), vec![pair("p1", "u1")]);
+
+        reloaded.drop_index("users", "/email").await.unwrap();
+        assert!(matches!(reloaded.find_by_index("users", "/email", &json!("ann@y.io")).await, Err(Error::IndexNotFound)));
+        assert!(matches!(reloaded.drop_index("users", "/email").await, Err(Error::IndexNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_text_search_follows_writes() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set("p1", "notes", "n1", json!({"body": "Quarterly budget review"})).await.unwrap();
+        assert!(matches!(store.search("p1", "notes", "budget").await, Err(Error::IndexNotFound)));
+
+        // Enabling search indexes existing values too
+        store.enable_search("notes").await.unwrap();
+        store.set("p1", "notes", "n2", json!({"body": "Budget, budget, budget"})).await.unwrap();
+        let hits = store.search("p1", "notes", "budget").await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.key.as_str()).collect::<Vec<_>>(), vec!["n2", "n1"]);
+
+        store.delete("p1", "notes", "n2").await.unwrap();
+        store.move_key("p1", "p2", "notes", "n1").await.unwrap();
+        assert!(store.search("p1", "notes", "budget").await.unwrap().is_empty());
+        assert_eq!(store.search("p2", "notes", "review").await.unwrap()[0].key, "n1");
+
+        store.disable_search("notes").await.unwrap();
+        assert!(matches!(store.search("p2", "notes", "review").await, Err(Error::IndexNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_groups() {
+        use crate::Predicate;
+
+        let store = MemStore::new(HashMap::new(), None);
+        store.set("p1", "settings", "prefs", json!({"theme": "dark", "font": 12})).await.unwrap();
+        store.set("p2", "settings", "prefs", json!({"theme": "dark", "font": 16})).await.unwrap();
+        store.set("p3", "settings", "prefs", json!({"theme": "light", "font": 14})).await.unwrap();
+        store.set("p4", "settings", "prefs", json!({"font": "huge"})).await.unwrap();
+        store.set("p1", "settings", "other", json!({"theme": "dark"})).await.unwrap();
+
+        let by_theme = Aggregation {
+            key: Some("prefs".to_string()),
+            group_by: Some("/theme".to_string()),
+            sum: vec!["/font".to_string()],
+            min: vec!["/font".to_string()],
+            max: vec!["/font".to_string()],
+            ..Default::default()
+        };
+        let groups = store.aggregate("settings", &by_theme).await.unwrap();
+        assert_eq!(groups.len(), 3);
+        let dark = groups.iter().find(|g| g.group == Some(json!("dark"))).unwrap();
+        assert_eq!(dark.count, 2);
+        assert_eq!(dark.sum["/font"], 28.0);
+        assert_eq!((&dark.min["/font"], &dark.max["/font"]), (&json!(12), &json!(16)));
+        let missing = groups.iter().find(|g| g.group.is_none()).unwrap();
+        assert_eq!((missing.count, missing.sum.get("/font")), (1, None));
+
+        let light = Aggregation {
+            filter: Some(Predicate::Eq { path: "/theme".to_string(), value: json!("light") }),
+            ..Default::default()
+        };
+        assert_eq!(store.aggregate("settings", &light).await.unwrap()[0].count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_schema_enforced_on_writes() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set("p1", "stats", "legacy", json!("free text")).await.unwrap();
+        store.set_schema("stats", json!({"type": "integer", "maximum": 3})).await.unwrap();
+        assert!(matches!(store.set_schema("stats", json!([1])).await, Err(Error::WrongType(_))));
+        match store.set_schema("stats", json!({"type": "string", "pattern": "^a"})).await {
+            Err(Error::ValidationFailed(problems)) => assert_eq!(problems, vec!["/: unsupported keyword pattern"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        store.set("p1", "stats", "hits", json!(2)).await.unwrap();
+        assert_eq!(store.incr("p1", "stats", "hits", 1).await.unwrap(), 3);
+        match store.incr("p1", "stats", "hits", 1).await {
+            Err(Error::ValidationFailed(errors)) => assert_eq!(errors, vec!["/: expected at most 3"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(store.get("p1", "stats", "hits").await.unwrap(), json!(3));
+
+        // Batch writes are all-or-nothing
+        let batch = vec![("p1", "stats", "a", json!(1)), ("p2", "stats", "b", json!("x"))];
+        assert!(matches!(store.set_many(batch).await, Err(Error::ValidationFailed(_))));
+        assert!(matches!(store.get("p1", "stats", "a").await, Err(Error::KeyNotFound)));
+
+        // A value stored before the schema stays put rather than being lost mid-move
+        assert!(matches!(store.move_key("p1", "p2", "stats", "legacy").await, Err(Error::ValidationFailed(_))));
+        assert_eq!(store.get("p1", "stats", "legacy").await.unwrap(), json!("free text"));
+
+        store.remove_schema("stats").await.unwrap();
+        store.set("p1", "stats", "hits", json!("anything")).await.unwrap();
+        assert!(matches!(store.get_schema("stats").await, Err(Error::KeyNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_history_and_revert() {
+        let store = MemStore::new(HashMap::new(), None).with_history(HistoryRetention::Versions(3));
+        store.set("p1", "settings", "theme", json!("light")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let before_overwrite = unix_millis();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        store.set("p1", "settings", "theme", json!("dark")).await.unwrap();
+        store.set("p1", "settings", "theme", json!("dim")).await.unwrap();
+
+        let history = store.history("p1", "settings", "theme").await.unwrap();
+        assert_eq!(history.iter().map(|e| e.value.clone()).collect::<Vec<_>>(), vec![json!("dim"), json!("dark"), json!("light")]);
+        assert_eq!(store.get_at("p1", "settings", "theme", before_overwrite).await.unwrap(), json!("light"));
+        assert_eq!(store.get_at("p1", "settings", "theme", unix_millis()).await.unwrap(), json!("dim"));
+        assert!(matches!(store.get_at("p1", "settings", "theme", 0).await, Err(Error::KeyNotFound)));
+
+        store.revert("p1", "settings", "theme", before_overwrite).await.unwrap();
+        assert_eq!(store.get("p1", "settings", "theme").await.unwrap(), json!("light"));
+        // The revert is a version of its own, so the original write fell out of retention
+        let history = store.history("p1", "settings", "theme").await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].value, json!("light"));
+        assert!(matches!(store.revert("p1", "settings", "theme", before_overwrite).await, Err(Error::KeyNotFound)));
+
+        // A delete keeps the log and adds a tombstone, so the key can be brought back
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let before_delete = unix_millis();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        store.delete("p1", "settings", "theme").await.unwrap();
+        let history = store.history("p1", "settings", "theme").await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(history[0].deleted);
+        assert_eq!(history[1].value, json!("light"));
+        assert!(matches!(store.get_at("p1", "settings", "theme", unix_millis()).await, Err(Error::KeyNotFound)));
+        store.revert("p1", "settings", "theme", before_delete).await.unwrap();
+        assert_eq!(store.get("p1", "settings", "theme").await.unwrap(), json!("light"));
+
+        // Deletes into the trash are recorded the same way
+        let trashing = MemStore::new(HashMap::new(), None)
+            .with_history(HistoryRetention::Versions(3))
+            .with_trash(Duration::from_secs(60));
+        trashing.set("p1", "settings", "theme", json!("light")).await.unwrap();
+        trashing.delete("p1", "settings", "theme").await.unwrap();
+        let history = trashing.history("p1", "settings", "theme").await.unwrap();
+        assert_eq!(history.iter().map(|e| e.deleted).collect::<Vec<_>>(), vec![true, false]);
+
+        let untracked = MemStore::new(HashMap::new(), None);
+        untracked.set("p1", "settings", "theme", json!("light")).await.unwrap();
+        assert!(untracked.history("p1", "settings", "theme").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trash_restore_and_purge() {
+        let store = MemStore::new(HashMap::new(), None).with_trash(Duration::from_secs(60));
+        store.set("p1", "notes", "n1", json!("first")).await.unwrap();
+        store.set("p1", "notes", "n2", json!("second")).await.unwrap();
+        store.delete("p1", "notes", "n1").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        store.delete("p1", "notes", "n2").await.unwrap();
+        assert!(matches!(store.get("p1", "notes", "n1").await, Err(Error::KeyNotFound)));
+
+        let trash = store.list_trash("p1").await.unwrap();
+        assert_eq!(trash.len(), 2);
+        assert_eq!((trash[0].app_id.as_str(), trash[0].value.clone()), ("notes", json!("second")));
+        assert_eq!(trash[0].expires_at, trash[0].deleted_at + 60_000);
+
+        store.restore("p1", "notes", "n1").await.unwrap();
+        assert_eq!(store.get("p1", "notes", "n1").await.unwrap(), json!("first"));
+        assert!(matches!(store.restore("p1", "notes", "n1").await, Err(Error::KeyNotFound)));
+
+        store.set("p1", "notes", "n2", json!("rewritten")).await.unwrap();
+        assert!(matches!(store.restore("p1", "notes", "n2").await, Err(Error::AlreadyExists)));
+        store.purge("p1", "notes", "n2").await.unwrap();
+        assert!(store.list_trash("p1").await.unwrap().is_empty());
+
+        // Expired entries are no longer listed or restorable
+        let store = MemStore::new(HashMap::new(), None).with_trash(Duration::ZERO);
+        store.set("p1", "notes", "n1", json!("gone")).await.unwrap();
+        store.delete("p1", "notes", "n1").await.unwrap();
+        assert!(store.list_trash("p1").await.unwrap().is_empty());
+        assert!(matches!(store.restore("p1", "notes", "n1").await, Err(Error::KeyNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_write_hooks() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.on_before_set(HookScope::default().app("users"), |_, _, _, mut value| {
+            match value.get("email").and_then(|e| e.as_str()) {
+                Some(email) => {
+                    let lower = email.to_lowercase();
+                    value["email"] = json!(lower);
+                    Ok(value)
+                }
+                None => Err(Error::ValidationFailed(vec!["/email: required".to_string()])),
+            }
+        });
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        store.on_after_set(HookScope::default().key_prefix("u"), move |e| seen.lock().unwrap().push((e.kind, e.key.clone(), e.value.clone())));
+        let seen = events.clone();
+        store.on_after_delete(HookScope::default().persona("p1"), move |e| seen.lock().unwrap().push((e.kind, e.key.clone(), e.value.clone())));
+
+        store.set("p1", "users", "u1", json!({"email": "Ann@Example.com"})).await.unwrap();
+        assert_eq!(store.get("p1", "users", "u1").await.unwrap(), json!({"email": "ann@example.com"}));
+        assert!(matches!(store.set("p1", "users", "u2", json!({})).await, Err(Error::ValidationFailed(_))));
+        // Rewriting the same value after transformation is not a change
+        store.set("p1", "users", "u1", json!({"email": "ANN@example.com"})).await.unwrap();
+        store.set("p1", "other", "x1", json!(1)).await.unwrap();
+        store.delete("p1", "users", "u1").await.unwrap();
+        store.delete("p1", "other", "x1").await.unwrap();
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            (ChangeKind::Set, "u1".to_string(), Some(json!({"email": "ann@example.com"}))),
+            (ChangeKind::Delete, "u1".to_string(), None),
+            (ChangeKind::Delete, "x1".to_string(), None),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_after_hooks_cover_bulk_changes() {
+        let store = MemStore::new(HashMap::new(), None);
+        store.set("p1", "notes", "n1", json!("hi")).await.unwrap();
+        store.set("p1", "todos", "t1", json!("write docs")).await.unwrap();
+        store.define_derived("p1", "stats", "todos", Derivation::Count { persona_id: "p1".to_string(), app_id: "todos".to_string() }).await.unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        store.on_after_set(HookScope::default().app("notes"), move |e| seen.lock().unwrap().push((e.kind, e.persona_id.clone(), e.key.clone())));
+        let seen = events.clone();
+        store.on_after_delete(HookScope::default().app("notes"), move |e| seen.lock().unwrap().push((e.kind, e.persona_id.clone(), e.key.clone())));
+        let seen = events.clone();
+        store.on_after_set(HookScope::default().app("stats"), move |e| seen.lock().unwrap().push((e.kind, e.persona_id.clone(), e.key.clone())));
+        let seen = events.clone();
+        store.on_after_delete(HookScope::default().app("stats"), move |e| seen.lock().unwrap().push((e.kind, e.persona_id.clone(), e.key.clone())));
+        let take = || std::mem::take(&mut *events.lock().unwrap());
+
+        // A recomputed derived key fires its hooks like a direct write
+        store.set("p1", "todos", "t2", json!("ship")).await.unwrap();
+        assert_eq!(take(), vec![(ChangeKind::Set, "p1".to_string(), "todos".to_string())]);
+
+        store.move_app("p1", "p2", "notes").await.unwrap();
+        assert_eq!(take(), vec![
+            (ChangeKind::Delete, "p1".to_string(), "n1".to_string()),
+            (ChangeKind::Set, "p2".to_string(), "n1".to_string()),
+        ]);
+        store.copy_persona("p2", "p3").await.unwrap();
+        assert_eq!(take(), vec![(ChangeKind::Set, "p3".to_string(), "n1".to_string())]);
+        store.rename_persona("p3", "p4").await.unwrap();
+        assert_eq!(take(), vec![
+            (ChangeKind::Delete, "p3".to_string(), "n1".to_string()),
+            (ChangeKind::Set, "p4".to_string(), "n1".to_string()),
+        ]);
+        store.delete_app("p4", "notes").await.unwrap();
+        assert_eq!(take(), vec![(ChangeKind::Delete, "p4".to_string(), "n1".to_string())]);
+        store.delete_persona("p2").await.unwrap();
+        assert_eq!(take(), vec![(ChangeKind::Delete, "p2".to_string(), "n1".to_string())]);
+
+        // Dropping a derived key removes its value, and deleting its inputs recomputes it
+        store.delete_app("p1", "todos").await.unwrap();
+        assert_eq!(take(), vec![(ChangeKind::Set, "p1".to_string(), "todos".to_string())]);
+        store.drop_derived("p1", "stats", "todos").await.unwrap();
+        assert_eq!(take(), vec![(ChangeKind::Delete, "p1".to_string(), "todos".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_derived_keys_follow_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Arc::new(Persistence::new(dir.path()).unwrap());
+        let store = MemStore::new(HashMap::new(), Some(persistence.clone()));
+        store.set("p1", "todos", "t1", json!("write docs")).await.unwrap();
+        store.set(SYSTEM_PERSONA, "config", "defaults", json!({"theme": "light", "limits": {"max": 5}})).await.unwrap();
+
+        store.define_derived("p1", "stats", "todo_count", Derivation::Count { persona_id: "p1".to_string(), app_id: "todos".to_string() }).await.unwrap();
+        let sources = vec![KeyRef::new(SYSTEM_PERSONA, "config", "defaults"), KeyRef::new("p1", "config", "overrides")];
+        store.define_derived("p1", "config", "effective", Derivation::Merge { sources }).await.unwrap();
+        assert_eq!(store.get("p1", "stats", "todo_count").await.unwrap(), json!(1));
+        assert_eq!(store.get("p1", "config", "effective").await.unwrap(), json!({"theme": "light", "limits": {"max": 5}}));
+
+        store.set("p1", "todos", "t2", json!("ship it")).await.unwrap();
+        store.set("p1", "config", "overrides", json!({"limits": {"max": 10}})).await.unwrap();
+        assert_eq!(store.get("p1", "stats", "todo_count").await.unwrap(), json!(2));
+        assert_eq!(store.get("p1", "config", "effective").await.unwrap(), json!({"theme": "light", "limits": {"max": 10}}));
+
+        // Inputs in another persona still persist the derived key's persona
+        store.set(SYSTEM_PERSONA, "config", "defaults", json!({"theme": "dark"})).await.unwrap();
+        store.delete_app("p1", "todos").await.unwrap();
+        store.wait().await;
+        let saved = persistence.load_all().unwrap();
+        assert_eq!(saved["p1"]["config"]["effective"], json!({"theme": "dark", "limits": {"max": 10}}));
+        assert_eq!(saved["p1"]["stats"]["todo_count"], json!(0));
+
+        assert_eq!(store.list_derived().await.unwrap().len(), 2);
+        store.drop_derived("p1", "stats", "todo_count").await.unwrap();
+        assert!(matches!(store.get("p1", "stats", "todo_count").await, Err(Error::KeyNotFound)));
+        assert!(matches!(store.drop_derived("p1", "stats", "todo_count").await, Err(Error::KeyNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_derived_keys_are_guarded() {
+        let store = MemStore::new(HashMap::new(), None).with_trash(Duration::from_secs(60));
+        store.set("p1", "todos", "t1", json!("write docs")).await.unwrap();
+        store.set("p1", "stats", "manual", json!(7)).await.unwrap();
+        let count = || Derivation::Count { persona_id: "p1".to_string(), app_id: "todos".to_string() };
+
+        // A plain key is never silently taken over
+        assert!(matches!(store.define_derived("p1", "stats", "manual", count()).await, Err(Error::AlreadyExists)));
+        assert_eq!(store.get("p1", "stats", "manual").await.unwrap(), json!(7));
+
+        // Derived keys are only written by the store, and redefining one is allowed
+        store.define_derived("p1", "stats", "todos", count()).await.unwrap();
+        store.define_derived("p1", "stats", "todos", count()).await.unwrap();
+        assert!(matches!(store.set("p1", "stats", "todos", json!(9)).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.incr("p1", "stats", "todos", 1).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.delete("p1", "stats", "todos").await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.set_many(vec![("p1", "stats", "todos", json!(9))]).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.move_key("p1", "p2", "stats", "todos").await, Err(Error::ReadOnly(_))));
+        assert_eq!(store.get("p1", "stats", "todos").await.unwrap(), json!(1));
+
+        // Definitions cannot be written directly, which would skip the checks above
+        let definition = serde_json::to_value(DerivedKey { target: KeyRef::new("p1", "stats", "manual"), derivation: count() }).unwrap();
+        let id = derived::definition_key(&KeyRef::new("p1", "stats", "manual"));
+        assert!(matches!(store.set(SYSTEM_PERSONA, derived::DERIVED_APP, &id, definition.clone()).await, Err(Error::ReadOnly(_))));
+        assert!(matches!(store.set_many(vec![(SYSTEM_PERSONA, derived::DERIVED_APP, id.as_str(), definition)]).await, Err(Error::ReadOnly(_))));
+        assert_eq!(store.get("p1", "stats", "manual").await.unwrap(), json!(7));
+
+        // Computed values must satisfy the target app's schema
+        store.set_schema("limits", json!({"type": "integer", "maximum": 1})).await.unwrap();
+        store.define_derived("p1", "limits", "todos", count()).await.unwrap();
+        store.set("p1", "todos", "t2", json!("ship")).await.unwrap();
+        assert!(matches!(store.get("p1", "limits", "todos").await, Err(Error::KeyNotFound)));
+        assert!(matches!(store.define_derived("p1", "limits", "other", count()).await, Err(Error::ValidationFailed(_))));
+        store.delete("p1", "todos", "t2").await.unwrap();
+        assert_eq!(store.get("p1", "limits", "todos").await.unwrap(), json!(1));
+    }
+
     #[tokio::test]
     async fn test_move_key() {
         let store = MemStore::new(HashMap::new(), None);
@@ -271,6 +2351,38 @@ mod tests {
         assert_eq!(store.get("p2", "app1", "k1").await.unwrap(), json!("v1"));
     }
 
+    #[tokio::test]
+    async fn test_persona_and_app_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Arc::new(Persistence::new(dir.path()).unwrap());
+        let store = MemStore::new(HashMap::new(), Some(persistence));
+        store.set("alice", "notes", "n1", json!("hi")).await.unwrap();
+        store.enqueue("alice", "jobs", "q", json!(1)).await.unwrap();
+
+        store.copy_persona("alice", "backup").await.unwrap();
+        assert!(matches!(store.copy_persona("alice", "backup").await, Err(Error::AlreadyExists)));
+        store.rename_persona("backup", "carol").await.unwrap();
+        assert_eq!(store.get("carol", "notes", "n1").await.unwrap(), json!("hi"));
+
+        // Queues live in a sidecar app and travel with their app
+        store.move_app("alice", "bob", "jobs").await.unwrap();
+        assert!(store.dequeue("bob", "jobs", "q", Duration::from_secs(30), Duration::ZERO).await.unwrap().is_some());
+        assert!(!store.get_apps("alice").await.unwrap().iter().any(|a| a.starts_with("jobs")));
+
+        // Emptied apps and personas are no longer listed, and their file goes away
+        store.delete("alice", "notes", "n1").await.unwrap();
+        store.wait().await;
+        assert!(store.get_apps("alice").await.unwrap().is_empty());
+        assert!(!store.get_personas().await.unwrap().contains(&"alice".to_string()));
+        assert!(!dir.path().join("alice.json").exists());
+
+        store.delete_app("carol", "notes").await.unwrap();
+        store.delete_persona("bob").await.unwrap();
+        store.wait().await;
+        assert!(!dir.path().join("bob.json").exists());
+        assert!(matches!(store.delete_persona("bob").await, Err(Error::PersonaNotFound)));
+    }
+
     #[tokio::test]
     async fn test_app_scope_and_vault() {
         let store = MemStore::new(HashMap::new(), None);
@@ -286,11 +2398,115 @@ mod tests {
         v.set("password", "topsecret").await.unwrap();
 
         let pass = v.get("password").await.unwrap();
-        assert_eq!(pass, "topsecret");
+        assert_eq!(pass.expose(), "topsecret");
 
         // Check that it's encrypted in the underlying store
         let raw = scope.get("password").await.unwrap();
         assert_ne!(raw, json!("topsecret"));
         assert!(raw.is_string());
     }
+
+    #[tokio::test]
+    async fn test_vault_blind_index_lookup() {
+        let store = MemStore::new(HashMap::new(), None);
+        let master_key = b"thisis32byteslongsecretkey123456";
+
+        store.app("p1", "users").vault(master_key).set_indexed("u1", "alice@example.com").await.unwrap();
+        store.app("p1", "users").vault(master_key).set_indexed("u2", "bob@example.com").await.unwrap();
+        store.app("p2", "users").vault(master_key).set_indexed("u9", "alice@example.com").await.unwrap();
+
+        let v = store.app("p1", "users");
+        let v = v.vault(master_key);
+        assert_eq!(v.lookup("alice@example.com").await.unwrap(), vec!["u1".to_string()]);
+        assert!(v.lookup("carol@example.com").await.unwrap().is_empty());
+
+        let digest = vault::blind_index("alice@example.com", master_key).unwrap();
+        let found = store.find_blind("users", &digest).await.unwrap();
+        assert_eq!(found, vec![("p1".to_string(), "u1".to_string()), ("p2".to_string(), "u9".to_string())]);
+
+        // Overwriting without an index removes the stale digest
+        v.set("u1", "changed@example.com").await.unwrap();
+        assert!(v.lookup("alice@example.com").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_vault_list_delete_and_metadata() {
+        let store = MemStore::new(HashMap::new(), None);
+        let master_key = b"thisis32byteslongsecretkey123456";
+
+        let scope = store.app("p1", "a1");
+        scope.set("plain", json!("not a secret")).await.unwrap();
+
+        let v = scope.vault(master_key);
+        assert!(v.list().await.unwrap().is_empty());
+        v.set("b", "two").await.unwrap();
+        v.set_indexed("a", "one").await.unwrap();
+        assert_eq!(v.list().await.unwrap(), vec!["a".to_string(), "b".to_string()]);
+        assert!(v.exists("a").await.unwrap());
+        assert!(!v.exists("plain").await.unwrap());
+
+        let meta = v.metadata("a").await.unwrap();
+        assert_eq!(meta.key_id, vault::key_id(master_key).unwrap());
+        assert!(meta.updated_at >= meta.created_at);
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        v.set("a", "uno").await.unwrap();
+        let updated = v.metadata("a").await.unwrap();
+        assert_eq!(updated.created_at, meta.created_at);
+        assert!(updated.updated_at > meta.updated_at);
+
+        v.delete("a").await.unwrap();
+        assert!(!v.exists("a").await.unwrap());
+        assert!(matches!(v.get("a").await, Err(Error::KeyNotFound)));
+        assert_eq!(v.list().await.unwrap(), vec!["b".to_string()]);
+        assert_eq!(scope.get("plain").await.unwrap(), json!("not a secret"));
+
+        // Entries written without metadata are still vault entries
+        scope.set("legacy", json!(vault::encrypt("old", master_key).unwrap())).await.unwrap();
+        assert_eq!(v.list().await.unwrap(), vec!["b".to_string(), "legacy".to_string()]);
+        assert!(v.exists("legacy").await.unwrap());
+        let legacy = v.metadata("legacy").await.unwrap();
+        assert_eq!(legacy.key_id, vault::key_id(master_key).unwrap());
+        assert!(legacy.created_at > 0);
+
+        // A plain write over a vault entry drops its metadata instead of leaving it stale
+        scope.set("b", json!("plain now")).await.unwrap();
+        assert!(!v.exists("b").await.unwrap());
+        assert!(store.get("p1", &vault::meta_app("a1"), "b").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_vault_grant_and_revoke() {
+        let store = MemStore::new(HashMap::new(), None);
+        let key_a = b"thisis32byteslongsecretkey123456";
+        let key_b = b"another32byteslongsecretkey65432";
+
+        let app_a = store.app("alice", "shared");
+        let vault_a = app_a.vault(key_a);
+        let app_b = store.app("bob", "shared");
+        let vault_b = app_b.vault(key_b);
+
+        // Alice only ever sees the key Bob derived for her, never Bob's master key
+        let recipient_key = vault_b.recipient_key("alice").unwrap();
+        vault_a.set("api_token", "tok-123").await.unwrap();
+        vault_a.grant("api_token", "bob", recipient_key.expose()).await.unwrap();
+        assert_eq!(vault_b.get_grant("alice", "api_token").await.unwrap().expose(), "tok-123");
+
+        // The grant is not plaintext and cannot be opened with Alice's key
+        assert!(app_b.vault(key_a).get_grant("alice", "api_token").await.is_err());
+        let raw = store.get("bob", "shared::grants", &vault::grant_key("alice", "api_token")).await.unwrap();
+        assert_ne!(raw, json!("tok-123"));
+
+        // Updating the secret updates the grant, and deleting it revokes the grant
+        vault_a.set("api_token", "tok-456").await.unwrap();
+        assert_eq!(vault_b.get_grant("alice", "api_token").await.unwrap().expose(), "tok-456");
+        vault_a.delete("api_token").await.unwrap();
+        assert!(matches!(vault_b.get_grant("alice", "api_token").await, Err(Error::KeyNotFound)));
+
+        vault_a.set("other", "x").await.unwrap();
+        vault_a.grant("other", "bob", recipient_key.expose()).await.unwrap();
+        vault_a.revoke("other", "bob").await.unwrap();
+        assert!(matches!(vault_b.get_grant("alice", "other").await, Err(Error::KeyNotFound)));
+        assert!(store.get("alice", &vault::granted_app("shared"), "other").await.is_err());
+    }
 }
@@ -4,8 +4,65 @@
 pub mod memstore;
 /// Filesystem persistence logic.
 pub mod persistence;
+/// Durable per-app message queues.
+pub mod queue;
+/// Distributed locks with fencing tokens.
+pub mod lock;
+/// Per-key version and timestamp metadata.
+pub mod keymeta;
+/// Past versions of keys.
+pub mod history;
+/// Hooks run around writes.
+pub mod hooks;
+/// Soft-deleted keys awaiting restore or purge.
+pub mod trash;
+/// Layered configuration lookups through parent personas.
+pub mod layers;
+/// Secondary indexes on JSON fields.
+pub mod index;
+/// Keys computed from other keys.
+pub mod derived;
+/// Full-text search over string values.
+pub mod fulltext;
+/// JSON Schema validation of app values.
+pub mod schema;
 /// Cryptographic utilities for client-side encryption.
 pub mod vault;
 
-pub use memstore::MemStore;
+pub use memstore::{MemStore, PersonaData, StoreData};
 pub use persistence::Persistence;
+
+/// Returns whether `name` is `app_id` itself or one of its `app_id::...` sidecar apps.
+pub(crate) fn belongs_to_app(name: &str, app_id: &str) -> bool {
+    name.strip_prefix(app_id)
+        .map(|rest| rest.is_empty() || rest.starts_with("::"))
+        .unwrap_or(false)
+}
+
+/// Checks that an app ID given by a caller names a regular app rather than a sidecar.
+pub(crate) fn check_app_id(app_id: &str) -> crate::Result<()> {
+    if app_id.contains("::") {
+        return Err(crate::Error::InvalidAppId(format!("{} contains \"::\", which is reserved for internal apps", app_id)));
+    }
+    Ok(())
+}
+
+/// Like [`check_app_id`], but also accepts the grant records of an app, which vault
+/// scopes keep through the regular key-value API.
+pub(crate) fn check_kv_app_id(app_id: &str) -> crate::Result<()> {
+    let base = [vault::GRANTS_SUFFIX, vault::GRANTED_SUFFIX].iter()
+        .find_map(|suffix| app_id.strip_suffix(suffix))
+        .unwrap_or(app_id);
+    if base.contains("::") {
+        return check_app_id(app_id);
+    }
+    Ok(())
+}
+
+/// Returns the current time as milliseconds since the Unix epoch.
+pub(crate) fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -1,18 +1,31 @@
 use std::collections::HashMap;
+use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use crate::{Result, Error};
+use crate::engine::vault::{self, SecretBytes, SecretString};
+use crate::engine::memstore::{PersonaData, StoreData};
 use log::warn;
 
 #[allow(unused_imports)]
 use crate::engine::MemStore;
 
+/// Environment variable holding a hex-encoded 32-byte key for at-rest encryption.
+pub const DATA_KEY_ENV: &str = "CELERIX_DATA_KEY";
+/// Environment variable pointing to a file containing the at-rest encryption key.
+pub const DATA_KEY_FILE_ENV: &str = "CELERIX_DATA_KEY_FILE";
+
+const PLAIN_EXT: &str = "json";
+const ENCRYPTED_EXT: &str = "enc";
+
 /// Handles disk I/O for the [`MemStore`].
 /// 
 /// Persistence uses an atomic "write-then-rename" strategy to ensure data integrity.
-/// Each persona is stored in its own `.json` file.
+/// Each persona is stored in its own `.json` file, or in an AES-256-GCM encrypted
+/// `.enc` file when a data key is configured.
 pub struct Persistence {
     data_dir: PathBuf,
+    key: Option<SecretBytes>,
 }
 
 impl Persistence {
@@ -24,18 +37,51 @@ impl Persistence {
         if !dir.exists() {
             fs::create_dir_all(&dir)?;
         }
-        Ok(Self { data_dir: dir })
+        Ok(Self { data_dir: dir, key: None })
     }
 
-    /// Writes a single persona's data to a JSON file atomically.
+    /// Initializes a `Persistence` handler that encrypts persona files at rest.
+    /// 
+    /// The `key` must be 32 bytes. A directory holding plain `.json` files has to
+    /// be converted with [`Persistence::migrate_to`] first, otherwise
+    /// [`Persistence::load_all`] refuses to load it.
+    pub fn with_encryption<P: AsRef<Path>>(dir: P, key: &[u8]) -> Result<Self> {
+        if key.len() != 32 {
+            return Err(Error::Internal("Key must be 32 bytes".to_string()));
+        }
+        let mut p = Self::new(dir)?;
+        p.key = Some(SecretBytes::new(key.to_vec()));
+        Ok(p)
+    }
+
+    /// Returns `true` if persona files are encrypted at rest.
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some()
+    }
+
+    fn ext(&self) -> &'static str {
+        if self.key.is_some() { ENCRYPTED_EXT } else { PLAIN_EXT }
+    }
+
+    fn persona_path(&self, persona_id: &str, ext: &str) -> PathBuf {
+        self.data_dir.join(format!("{}.{}", persona_id, ext))
+    }
+
+    /// Writes a single persona's data to disk atomically.
     /// 
     /// This method writes to a temporary file first and then renames it to the
     /// final destination, preventing file corruption during power failures.
-    pub fn save_persona(&self, persona_id: &str, data: &HashMap<String, HashMap<String, serde_json::Value>>) -> Result<()> {
-        let file_path = self.data_dir.join(format!("{}.json", persona_id));
-        let temp_path = file_path.with_extension("json.tmp");
+    /// Files in the other storage format are left alone.
+    pub fn save_persona(&self, persona_id: &str, data: &PersonaData) -> Result<()> {
+        let ext = self.ext();
+        let file_path = self.persona_path(persona_id, ext);
+        let temp_path = file_path.with_extension(format!("{}.tmp", ext));
 
-        let bytes = serde_json::to_vec_pretty(data)?;
+        let mut bytes = serde_json::to_vec_pretty(data)?;
+        if let Some(key) = &self.key {
+            let plaintext = SecretBytes::new(bytes);
+            bytes = vault::encrypt_bytes(plaintext.expose(), key.expose())?;
+        }
         
         fs::write(&temp_path, bytes)?;
         fs::rename(&temp_path, &file_path)?;
@@ -43,11 +89,30 @@ impl Persistence {
         Ok(())
     }
 
+    /// Removes a persona's file from disk.
+    /// 
+    /// A persona without any file is not an error.
+    pub fn delete_persona(&self, persona_id: &str) -> Result<()> {
+        let path = self.persona_path(persona_id, self.ext());
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
     /// Loads all persona data found in the data directory.
     /// 
-    /// Scans for all `.json` files in the `data_dir` and parses them into the
-    /// store's internal data structure.
-    pub fn load_all(&self) -> Result<HashMap<String, HashMap<String, HashMap<String, serde_json::Value>>>> {
+    /// Scans for all `.json` files (or `.enc` files, with a data key) in the `data_dir`
+    /// and parses them into the store's internal data structure.
+    /// 
+    /// Fails if the directory also holds files in the other storage format, or if an
+    /// encrypted file cannot be decrypted, since saving over such a directory would
+    /// lose the personas that could not be loaded.
+    pub fn load_all(&self) -> Result<StoreData> {
+        self.load(true)
+    }
+
+    fn load(&self, strict: bool) -> Result<StoreData> {
         let mut all_data = HashMap::new();
 
         if !self.data_dir.exists() {
@@ -58,34 +123,117 @@ impl Persistence {
             let entry = entry?;
             let path = entry.path();
             
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                let persona_id = path.file_stem()
-                    .and_then(|s| s.to_str())
-                    .ok_or_else(|| Error::Internal("Invalid filename".to_string()))?
-                    .to_string();
-
-                let content = match fs::read(&path) {
-                    Ok(c) => c,
-                    Err(e) => {
-                        warn!("Could not read persona file {:?}: {}", path, e);
-                        continue;
-                    }
-                };
-
-                let persona_data: HashMap<String, HashMap<String, serde_json::Value>> = match serde_json::from_slice(&content) {
-                    Ok(d) => d,
-                    Err(e) => {
-                        warn!("Could not unmarshal persona data from {:?}: {}", path, e);
-                        continue;
-                    }
-                };
-
-                all_data.insert(persona_id, persona_data);
+            let ext = match path.extension().and_then(|s| s.to_str()) {
+                Some(ext) if ext == PLAIN_EXT || ext == ENCRYPTED_EXT => ext,
+                _ => continue,
+            };
+
+            if ext != self.ext() {
+                if !strict {
+                    continue;
+                }
+                return Err(Error::Internal(if self.key.is_some() {
+                    format!("found plain persona file {:?} while encryption is enabled; convert the data directory with `celerix encrypt` first", path)
+                } else {
+                    format!("found encrypted persona file {:?} but no data key is configured; set {} or {}", path, DATA_KEY_ENV, DATA_KEY_FILE_ENV)
+                }));
+            }
+
+            let persona_id = path.file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or_else(|| Error::Internal("Invalid filename".to_string()))?
+                .to_string();
+
+            let mut content = match fs::read(&path) {
+                Ok(c) => SecretBytes::new(c),
+                Err(e) => {
+                    warn!("Could not read persona file {:?}: {}", path, e);
+                    continue;
+                }
+            };
+
+            if let Some(key) = &self.key {
+                content = vault::decrypt_bytes(content.expose(), key.expose())
+                    .map_err(|e| Error::Internal(format!("could not decrypt persona file {:?}: {}", path, e)))?;
             }
+
+            let persona_data: PersonaData = match serde_json::from_slice(content.expose()) {
+                Ok(d) => d,
+                Err(e) => {
+                    warn!("Could not unmarshal persona data from {:?}: {}", path, e);
+                    continue;
+                }
+            };
+
+            all_data.insert(persona_id, persona_data);
         }
 
         Ok(all_data)
     }
+
+    /// Rewrites every persona loaded by `self` through `target`.
+    /// 
+    /// Pointing both handlers at the same directory converts it in place between
+    /// the plain and encrypted formats: each source file is removed once its
+    /// persona has been written in the target format. Files already in the target
+    /// format are left alone, so an interrupted migration can simply be rerun.
+    /// Returns the number of personas written.
+    pub fn migrate_to(&self, target: &Persistence) -> Result<usize> {
+        let data = self.load(false)?;
+        let in_place = self.ext() != target.ext() && same_dir(&self.data_dir, &target.data_dir);
+        for (persona_id, persona_data) in &data {
+            target.save_persona(persona_id, persona_data)?;
+            if in_place {
+                fs::remove_file(self.persona_path(persona_id, self.ext()))?;
+            }
+        }
+        Ok(data.len())
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Reads a 32-byte at-rest key from a file.
+/// 
+/// The file may contain either the raw 32 bytes or 64 hex characters.
+pub fn read_key_file<P: AsRef<Path>>(path: P) -> Result<SecretBytes> {
+    let raw = SecretBytes::new(fs::read(path)?);
+    if raw.expose().len() == 32 {
+        return Ok(raw);
+    }
+    let hex_key = std::str::from_utf8(raw.expose()).map_err(|e| Error::Internal(format!("invalid data key: {}", e)))?;
+    parse_hex_key(hex_key.trim())
+}
+
+/// Resolves the at-rest key from [`DATA_KEY_ENV`] or [`DATA_KEY_FILE_ENV`].
+/// 
+/// Returns `Ok(None)` when neither variable is set.
+pub fn key_from_env() -> Result<Option<SecretBytes>> {
+    if let Ok(hex_key) = env::var(DATA_KEY_ENV) {
+        let hex_key = SecretString::new(hex_key);
+        if !hex_key.expose().is_empty() {
+            return parse_hex_key(hex_key.expose().trim()).map(Some);
+        }
+    }
+    if let Ok(path) = env::var(DATA_KEY_FILE_ENV) {
+        if !path.is_empty() {
+            return read_key_file(path).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+fn parse_hex_key(hex_key: &str) -> Result<SecretBytes> {
+    let key = SecretBytes::new(hex::decode(hex_key).map_err(|e| Error::Internal(format!("invalid data key: {}", e)))?);
+    if key.expose().len() != 32 {
+        return Err(Error::Internal("Key must be 32 bytes".to_string()));
+    }
+    Ok(key)
 }
 
 #[cfg(test)]
@@ -151,4 +299,58 @@ mod tests {
         assert_eq!(app.get("key_0").unwrap(), &json!(0));
         assert_eq!(app.get("key_1").unwrap(), &json!("string_val"));
     }
+
+    #[test]
+    fn test_encrypted_save_and_load() {
+        let dir = tempdir().unwrap();
+        let key = b"thisis32byteslongsecretkey123456";
+        let persistence = Persistence::with_encryption(dir.path(), key).unwrap();
+
+        let mut data = HashMap::new();
+        let mut app_data = HashMap::new();
+        app_data.insert("key1".to_string(), json!("value1"));
+        data.insert("app1".to_string(), app_data);
+
+        persistence.save_persona("p1", &data).unwrap();
+
+        let raw = fs::read(dir.path().join("p1.enc")).unwrap();
+        assert!(serde_json::from_slice::<serde_json::Value>(&raw).is_err());
+        assert!(!dir.path().join("p1.json").exists());
+
+        let loaded = persistence.load_all().unwrap();
+        assert_eq!(loaded.get("p1").unwrap().get("app1").unwrap().get("key1").unwrap(), &json!("value1"));
+
+        // Without the key (or with the wrong one) loading fails instead of skipping the persona
+        let plain = Persistence::new(dir.path()).unwrap();
+        assert!(plain.load_all().is_err());
+        let wrong = Persistence::with_encryption(dir.path(), b"thisis32byteslongsecretkey654321").unwrap();
+        assert!(wrong.load_all().is_err());
+
+        // A plain file next to encrypted ones is rejected too, and saving never removes it
+        fs::write(dir.path().join("p2.json"), r#"{"app1": {}}"#).unwrap();
+        assert!(persistence.load_all().is_err());
+        persistence.save_persona("p2", &data).unwrap();
+        assert!(dir.path().join("p2.json").exists());
+    }
+
+    #[test]
+    fn test_migrate_between_formats() {
+        let dir = tempdir().unwrap();
+        let key = b"thisis32byteslongsecretkey123456";
+        fs::write(dir.path().join("p1.json"), r#"{"app1": {"key1": "value1"}}"#).unwrap();
+
+        let plain = Persistence::new(dir.path()).unwrap();
+        let encrypted = Persistence::with_encryption(dir.path(), key).unwrap();
+
+        assert_eq!(plain.migrate_to(&encrypted).unwrap(), 1);
+        assert!(dir.path().join("p1.enc").exists());
+        assert!(!dir.path().join("p1.json").exists());
+
+        assert_eq!(encrypted.migrate_to(&plain).unwrap(), 1);
+        assert!(dir.path().join("p1.json").exists());
+        assert!(!dir.path().join("p1.enc").exists());
+
+        let loaded = plain.load_all().unwrap();
+        assert_eq!(loaded.get("p1").unwrap().get("app1").unwrap().get("key1").unwrap(), &json!("value1"));
+    }
 }
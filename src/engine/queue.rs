@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use crate::{Result, Error, QueueMessage};
+
+/// Default number of deliveries before a message is moved to the dead-letter list.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+/// Suffix of the sidecar app that holds the queues of an app.
+pub const QUEUE_SUFFIX: &str = "::queue";
+
+/// Returns the ID of the sidecar app holding the queues of `app_id`.
+pub fn queue_app(app_id: &str) -> String {
+    format!("{}{}", app_id, QUEUE_SUFFIX)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredMessage {
+    id: u64,
+    body: serde_json::Value,
+    attempts: u32,
+    /// Milliseconds since the Unix epoch before which the message is hidden
+    /// from consumers. `0` means the message has never been delivered.
+    visible_at: u64,
+}
+
+impl StoredMessage {
+    fn to_message(&self) -> QueueMessage {
+        QueueMessage {
+            id: self.id,
+            body: self.body.clone(),
+            attempts: self.attempts,
+            receipt: format!("{}:{}", self.id, self.attempts),
+        }
+    }
+}
+
+/// Splits a receipt into the message ID and the delivery it was issued for.
+fn parse_receipt(receipt: &str) -> Result<(u64, u32)> {
+    receipt.split_once(':')
+        .and_then(|(id, attempt)| Some((id.parse().ok()?, attempt.parse().ok()?)))
+        .ok_or(Error::MessageNotFound)
+}
+
+/// The persisted state of a single queue.
+/// 
+/// Messages are kept in FIFO order. A delivered message stays in place with a
+/// future `visible_at` until it is acknowledged, or becomes visible again when
+/// its visibility timeout expires or it is negatively acknowledged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueState {
+    next_id: u64,
+    messages: Vec<StoredMessage>,
+    dead: Vec<StoredMessage>,
+}
+
+impl QueueState {
+    /// Loads a queue from its stored JSON value; a missing value is an empty queue.
+    pub fn from_value(value: Option<&serde_json::Value>) -> Result<Self> {
+        match value {
+            None => Ok(Self::default()),
+            Some(v) => Self::deserialize(v).map_err(|_| Error::WrongType("value is not a queue".to_string())),
+        }
+    }
+
+    /// Serializes the queue for storage.
+    pub fn to_value(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    /// Appends a message and returns its ID.
+    pub fn enqueue(&mut self, body: serde_json::Value) -> u64 {
+        self.next_id += 1;
+        self.messages.push(StoredMessage { id: self.next_id, body, attempts: 0, visible_at: 0 });
+        self.next_id
+    }
+
+    /// Delivers the oldest visible message, hiding it until `now + visibility_ms`.
+    /// 
+    /// Messages whose visibility expired after `max_attempts` deliveries are moved
+    /// to the dead-letter list instead of being delivered again.
+    pub fn dequeue(&mut self, now: u64, visibility_ms: u64, max_attempts: u32) -> Option<QueueMessage> {
+        let mut i = 0;
+        while i < self.messages.len() {
+            let msg = &self.messages[i];
+            if msg.visible_at > now {
+                i += 1;
+                continue;
+            }
+            if msg.attempts >= max_attempts {
+                let dead = self.messages.remove(i);
+                self.dead.push(dead);
+                continue;
+            }
+            let msg = &mut self.messages[i];
+            msg.attempts += 1;
+            msg.visible_at = now + visibility_ms;
+            return Some(msg.to_message());
+        }
+        None
+    }
+
+    /// Returns when the next in-flight message becomes visible again, if any.
+    pub fn next_visible_at(&self, now: u64) -> Option<u64> {
+        self.messages.iter().map(|m| m.visible_at).filter(|&t| t > now).min()
+    }
+
+    /// Finds the message a receipt was issued for, as long as it was not delivered again since.
+    fn position(&self, receipt: &str) -> Result<usize> {
+        let (id, attempt) = parse_receipt(receipt)?;
+        self.messages.iter().position(|m| m.id == id && m.attempts == attempt).ok_or(Error::MessageNotFound)
+    }
+
+    /// Removes a processed message.
+    pub fn ack(&mut self, receipt: &str) -> Result<()> {
+        let pos = self.position(receipt)?;
+        self.messages.remove(pos);
+        Ok(())
+    }
+
+    /// Makes a delivered message visible again immediately, or dead-letters it
+    /// once it has been delivered `max_attempts` times.
+    pub fn nack(&mut self, receipt: &str, max_attempts: u32) -> Result<()> {
+        let pos = self.position(receipt)?;
+        if self.messages[pos].attempts >= max_attempts {
+            let dead = self.messages.remove(pos);
+            self.dead.push(dead);
+        } else {
+            self.messages[pos].visible_at = 0;
+        }
+        Ok(())
+    }
+
+    /// Returns the messages that exhausted their delivery attempts.
+    pub fn dead_letters(&self) -> Vec<QueueMessage> {
+        self.dead.iter().map(StoredMessage::to_message).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redelivery_and_dead_letter() {
+        let mut q = QueueState::default();
+        let id = q.enqueue(json!("job"));
+
+        let first = q.dequeue(1000, 100, 2).unwrap();
+        assert_eq!((first.id, first.attempts), (id, 1));
+        // Hidden while in flight
+        assert!(q.dequeue(1050, 100, 2).is_none());
+        assert_eq!(q.next_visible_at(1050), Some(1100));
+
+        // Visibility timeout expired: delivered again, and the first receipt no longer works
+        let second = q.dequeue(1100, 100, 2).unwrap();
+        assert_eq!(second.attempts, 2);
+        assert!(matches!(q.ack(&first.receipt), Err(Error::MessageNotFound)));
+        assert!(matches!(q.ack("garbage"), Err(Error::MessageNotFound)));
+
+        // Out of attempts: dead-lettered instead of delivered
+        assert!(q.dequeue(1300, 100, 2).is_none());
+        assert_eq!(q.dead_letters().len(), 1);
+        assert!(matches!(q.ack(&second.receipt), Err(Error::MessageNotFound)));
+    }
+}
@@ -0,0 +1,323 @@
+use std::cmp::Ordering;
+use crate::{Result, Error, SYSTEM_PERSONA};
+use crate::engine::memstore::StoreData;
+
+/// App within [`SYSTEM_PERSONA`] that maps each app ID to the JSON Schema its values must satisfy.
+pub const SCHEMAS_APP: &str = "_schemas";
+
+/// Returns the schema registered for `app_id`, if any.
+pub fn schema_for<'a>(data: &'a StoreData, app_id: &str) -> Option<&'a serde_json::Value> {
+    data.get(SYSTEM_PERSONA)?.get(SCHEMAS_APP)?.get(app_id)
+}
+
+/// Checks a value about to be written to `app_id` against the app's schema, if it has one.
+pub fn enforce(data: &StoreData, app_id: &str, value: &serde_json::Value) -> Result<()> {
+    let Some(schema) = schema_for(data, app_id) else { return Ok(()) };
+    let errors = validate(schema, value);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::ValidationFailed(errors))
+    }
+}
+
+/// Validates `value` against a JSON Schema and returns one `"<path>: <problem>"` entry per violation.
+///
+/// This is a deliberately small subset of JSON Schema: `type`, `enum`, `const`,
+/// `properties`, `required`, `additionalProperties`, `minProperties`, `maxProperties`,
+/// `items`, `minItems`, `maxItems`, `uniqueItems`, `minLength`, `maxLength`,
+/// `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`,
+/// `allOf`, `anyOf`, `oneOf` and `not`. Schemas using anything else, such as `pattern`
+/// or `$ref`, are refused up front by [`unsupported`]. Paths are JSON pointers into the
+/// value, with `/` for the value itself.
+pub fn validate(schema: &serde_json::Value, value: &serde_json::Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, "", &mut errors);
+    errors
+}
+
+/// Keywords [`validate`] understands, plus annotations that never affect validation.
+const KEYWORDS: &[&str] = &[
+    "type", "enum", "const", "properties", "required", "additionalProperties",
+    "minProperties", "maxProperties", "items", "minItems", "maxItems", "uniqueItems",
+    "minLength", "maxLength", "minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum",
+    "multipleOf", "allOf", "anyOf", "oneOf", "not",
+    "$schema", "$id", "$comment", "title", "description", "default", "examples",
+    "deprecated", "readOnly", "writeOnly",
+];
+
+/// Lists the parts of a schema that [`validate`] would silently ignore, as
+/// `"<path>: <problem>"` entries where paths are JSON pointers into the schema.
+///
+/// A schema is only safe to enforce when this is empty.
+pub fn unsupported(schema: &serde_json::Value) -> Vec<String> {
+    let mut problems = Vec::new();
+    scan(schema, "", &mut problems);
+    problems
+}
+
+fn scan(schema: &serde_json::Value, path: &str, problems: &mut Vec<String>) {
+    let at = if path.is_empty() { "/" } else { path };
+    let map = match schema {
+        serde_json::Value::Bool(_) => return,
+        serde_json::Value::Object(map) => map,
+        _ => return problems.push(format!("{}: expected a schema object or boolean", at)),
+    };
+    for (keyword, sub) in map {
+        let child = format!("{}/{}", path, keyword.replace('~', "~0").replace('/', "~1"));
+        match keyword.as_str() {
+            "properties" => match sub.as_object() {
+                Some(properties) => for (field, sub) in properties {
+                    scan(sub, &format!("{}/{}", child, field.replace('~', "~0").replace('/', "~1")), problems);
+                },
+                None => problems.push(format!("{}: expected an object", child)),
+            },
+            "additionalProperties" | "items" | "not" => scan(sub, &child, problems),
+            "allOf" | "anyOf" | "oneOf" => match sub.as_array() {
+                Some(list) => for (i, sub) in list.iter().enumerate() {
+                    scan(sub, &format!("{}/{}", child, i), problems);
+                },
+                None => problems.push(format!("{}: expected an array", child)),
+            },
+            k if KEYWORDS.contains(&k) => {}
+            k => problems.push(format!("{}: unsupported keyword {}", at, k)),
+        }
+    }
+}
+
+fn check(schema: &serde_json::Value, value: &serde_json::Value, path: &str, errors: &mut Vec<String>) {
+    let mut fail = |msg: String| errors.push(format!("{}: {}", if path.is_empty() { "/" } else { path }, msg));
+    let schema = match schema {
+        serde_json::Value::Bool(true) => return,
+        serde_json::Value::Bool(false) => return fail("no value is allowed".to_string()),
+        serde_json::Value::Object(map) => map,
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            serde_json::Value::String(t) => vec![t.as_str()],
+            serde_json::Value::Array(list) => list.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| has_type(value, t)) {
+            return fail(format!("expected {}", allowed.join(" or ")));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.iter().any(|o| json_eq(o, value)) {
+            fail("not one of the allowed values".to_string());
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if !json_eq(expected, value) {
+            fail(format!("expected {}", expected));
+        }
+    }
+
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+                for field in required.iter().filter_map(|f| f.as_str()) {
+                    if !map.contains_key(field) {
+                        fail(format!("missing required property {}", field));
+                    }
+                }
+            }
+            if let Some(n) = schema.get("minProperties").and_then(|n| n.as_u64()) {
+                if (map.len() as u64) < n {
+                    fail(format!("expected at least {} properties", n));
+                }
+            }
+            if let Some(n) = schema.get("maxProperties").and_then(|n| n.as_u64()) {
+                if map.len() as u64 > n {
+                    fail(format!("expected at most {} properties", n));
+                }
+            }
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            for (field, v) in map {
+                let child = format!("{}/{}", path, field.replace('~', "~0").replace('/', "~1"));
+                match properties.and_then(|p| p.get(field)) {
+                    Some(sub) => check(sub, v, &child, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(serde_json::Value::Bool(false)) => errors.push(format!("{}: property is not allowed", child)),
+                        Some(sub) => check(sub, v, &child, errors),
+                        None => {}
+                    },
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            if let Some(n) = schema.get("minItems").and_then(|n| n.as_u64()) {
+                if (items.len() as u64) < n {
+                    fail(format!("expected at least {} items", n));
+                }
+            }
+            if let Some(n) = schema.get("maxItems").and_then(|n| n.as_u64()) {
+                if items.len() as u64 > n {
+                    fail(format!("expected at most {} items", n));
+                }
+            }
+            if schema.get("uniqueItems").and_then(|u| u.as_bool()) == Some(true) {
+                let duplicate = items.iter().enumerate().any(|(i, a)| items[..i].iter().any(|b| json_eq(a, b)));
+                if duplicate {
+                    fail("items are not unique".to_string());
+                }
+            }
+            if let Some(sub) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(sub, item, &format!("{}/{}", path, i), errors);
+                }
+            }
+        }
+        serde_json::Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(n) = schema.get("minLength").and_then(|n| n.as_u64()) {
+                if len < n {
+                    fail(format!("expected at least {} characters", n));
+                }
+            }
+            if let Some(n) = schema.get("maxLength").and_then(|n| n.as_u64()) {
+                if len > n {
+                    fail(format!("expected at most {} characters", n));
+                }
+            }
+        }
+        serde_json::Value::Number(n) => {
+            let x = n.as_f64().unwrap_or_default();
+            let bound = |keyword: &str| schema.get(keyword).and_then(|b| b.as_f64());
+            if let Some(min) = bound("minimum").filter(|min| x < *min) {
+                fail(format!("expected at least {}", min));
+            }
+            if let Some(max) = bound("maximum").filter(|max| x > *max) {
+                fail(format!("expected at most {}", max));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| x <= *min) {
+                fail(format!("expected more than {}", min));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| x >= *max) {
+                fail(format!("expected less than {}", max));
+            }
+            // Compare with a tolerance so decimal steps like 0.1 are not thrown off by binary rounding
+            let off_step = |step: &f64| {
+                let q = x / step;
+                *step > 0.0 && (q - q.round()).abs() > 1e-9 * q.abs().max(1.0)
+            };
+            if let Some(step) = bound("multipleOf").filter(off_step) {
+                fail(format!("expected a multiple of {}", step));
+            }
+        }
+        _ => {}
+    }
+
+    let passes = |sub: &serde_json::Value| validate(sub, value).is_empty();
+    if let Some(all) = schema.get("allOf").and_then(|a| a.as_array()) {
+        for sub in all {
+            check(sub, value, path, errors);
+        }
+    }
+    let mut fail = |msg: &str| errors.push(format!("{}: {}", if path.is_empty() { "/" } else { path }, msg));
+    if let Some(any) = schema.get("anyOf").and_then(|a| a.as_array()) {
+        if !any.iter().any(passes) {
+            fail("does not match any of the allowed schemas");
+        }
+    }
+    if let Some(one) = schema.get("oneOf").and_then(|a| a.as_array()) {
+        if one.iter().filter(|sub| passes(sub)).count() != 1 {
+            fail("does not match exactly one of the allowed schemas");
+        }
+    }
+    if let Some(not) = schema.get("not") {
+        if passes(not) {
+            fail("matches a disallowed schema");
+        }
+    }
+}
+
+fn has_type(value: &serde_json::Value, expected: &str) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().map(|x| x.fract() == 0.0).unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// JSON equality where numbers compare by value, so `1` equals `1.0`.
+fn json_eq(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    match (a, b) {
+        (serde_json::Value::Number(x), serde_json::Value::Number(y)) => x.as_f64().partial_cmp(&y.as_f64()) == Some(Ordering::Equal),
+        (serde_json::Value::Array(x), serde_json::Value::Array(y)) => x.len() == y.len() && x.iter().zip(y).all(|(a, b)| json_eq(a, b)),
+        (serde_json::Value::Object(x), serde_json::Value::Object(y)) => x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).map(|w| json_eq(v, w)).unwrap_or(false)),
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_reports_paths() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "port"],
+            "additionalProperties": false,
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "port": {"type": "integer", "minimum": 1, "maximum": 65535},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}, "uniqueItems": true},
+                "mode": {"anyOf": [{"const": "fast"}, {"type": "integer"}]}
+            }
+        });
+        assert!(validate(&schema, &json!({"name": "svc", "port": 8080, "tags": ["a"], "mode": 3})).is_empty());
+
+        let errors = validate(&schema, &json!({"name": "", "port": 70000.0, "tags": ["a", "c", "a"], "mode": "slow", "x": 1}));
+        assert_eq!(errors, vec![
+            "/mode: does not match any of the allowed schemas",
+            "/name: expected at least 1 characters",
+            "/port: expected at most 65535",
+            "/tags: items are not unique",
+            "/tags/1: not one of the allowed values",
+            "/x: property is not allowed",
+        ]);
+        assert_eq!(validate(&schema, &json!([1])), vec!["/: expected object"]);
+    }
+
+    #[test]
+    fn test_multiple_of_decimal_steps() {
+        let schema = json!({"multipleOf": 0.1});
+        assert!(validate(&schema, &json!(0.3)).is_empty());
+        assert!(validate(&schema, &json!(12.7)).is_empty());
+        assert_eq!(validate(&schema, &json!(0.35)), vec!["/: expected a multiple of 0.1"]);
+        assert!(validate(&json!({"multipleOf": 3}), &json!(9)).is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_keywords() {
+        let schema = json!({
+            "title": "service",
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "pattern": "^[a-z]+$"},
+                "ref": {"$ref": "#/definitions/x"}
+            },
+            "anyOf": [{"format": "email"}, true],
+            "items": 3,
+            "dependentRequired": {"a": ["b"]}
+        });
+        assert_eq!(unsupported(&schema), vec![
+            "/anyOf/0: unsupported keyword format",
+            "/: unsupported keyword dependentRequired",
+            "/items: expected a schema object or boolean",
+            "/properties/name: unsupported keyword pattern",
+            "/properties/ref: unsupported keyword $ref",
+        ]);
+        assert!(unsupported(&json!({"type": "integer", "description": "port", "maximum": 3})).is_empty());
+    }
+}
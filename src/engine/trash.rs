@@ -0,0 +1,100 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::TrashEntry;
+use crate::engine::PersonaData;
+
+/// Environment variable holding the trash retention in seconds.
+pub const RETENTION_SECS_ENV: &str = "CELERIX_TRASH_RETENTION_SECS";
+
+/// Suffix of the sidecar app that holds the deleted keys of an app.
+pub const TRASH_SUFFIX: &str = "::trash";
+
+/// Returns the ID of the sidecar app holding the trash of `app_id`.
+pub fn trash_app(app_id: &str) -> String {
+    format!("{}{}", app_id, TRASH_SUFFIX)
+}
+
+/// Returns the trash retention of `secs`, or of [`RETENTION_SECS_ENV`] if not given.
+/// 
+/// `None` means the trash is off and deletes are final.
+pub fn retention(secs: Option<u64>) -> Option<Duration> {
+    secs.or_else(|| std::env::var(RETENTION_SECS_ENV).ok().and_then(|v| v.parse().ok()))
+        .map(Duration::from_secs)
+}
+
+/// A deleted value as stored in the trash sidecar.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trashed {
+    /// The value at the time of deletion.
+    pub value: serde_json::Value,
+    /// Milliseconds since the Unix epoch at which the key was deleted.
+    pub deleted_at: u64,
+}
+
+impl Trashed {
+    /// Loads an entry from its stored JSON value.
+    pub fn from_value(value: Option<&serde_json::Value>) -> Option<Self> {
+        value.and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Serializes the entry for storage.
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Returns when the entry is purged under `retention`.
+    pub fn expires_at(&self, retention: Duration) -> u64 {
+        self.deleted_at.saturating_add(u64::try_from(retention.as_millis()).unwrap_or(u64::MAX))
+    }
+}
+
+/// Returns the keys in the trash of `app_id` that expired by `now`.
+pub fn expired(persona: &PersonaData, app_id: &str, retention: Duration, now: u64) -> Vec<String> {
+    persona.get(&trash_app(app_id))
+        .map(|trash| trash.iter()
+            .filter(|(_, v)| Trashed::from_value(Some(v)).map(|t| t.expires_at(retention) <= now).unwrap_or(true))
+            .map(|(key, _)| key.clone())
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Lists the unexpired entries in all trash sidecars of a persona, most recently deleted first.
+pub fn list(persona: &PersonaData, retention: Duration, now: u64) -> Vec<TrashEntry> {
+    let mut entries: Vec<TrashEntry> = persona.iter()
+        .filter_map(|(name, trash)| name.strip_suffix(TRASH_SUFFIX).map(|app_id| (app_id, trash)))
+        .flat_map(|(app_id, trash)| trash.iter().filter_map(move |(key, v)| {
+            let trashed = Trashed::from_value(Some(v))?;
+            let expires_at = trashed.expires_at(retention);
+            (expires_at > now).then(|| TrashEntry {
+                app_id: app_id.to_string(),
+                key: key.clone(),
+                value: trashed.value,
+                deleted_at: trashed.deleted_at,
+                expires_at,
+            })
+        }))
+        .collect();
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then_with(|| (&a.app_id, &a.key).cmp(&(&b.app_id, &b.key))));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_list_and_expiry() {
+        let mut persona = PersonaData::new();
+        let trash = persona.entry(trash_app("notes")).or_default();
+        trash.insert("old".to_string(), Trashed { value: json!(1), deleted_at: 100 }.to_value());
+        trash.insert("new".to_string(), Trashed { value: json!(2), deleted_at: 900 }.to_value());
+        persona.entry("notes".to_string()).or_default().insert("live".to_string(), json!(3));
+
+        let retention = Duration::from_millis(500);
+        let listed = list(&persona, retention, 1000);
+        assert_eq!(listed.len(), 1);
+        assert_eq!((listed[0].app_id.as_str(), listed[0].key.as_str(), listed[0].expires_at), ("notes", "new", 1400));
+        assert_eq!(expired(&persona, "notes", retention, 1000), vec!["old".to_string()]);
+    }
+}
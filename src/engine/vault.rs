@@ -2,33 +2,203 @@ use aes_gcm::{
     aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Nonce,
 };
-use crate::{Result, Error};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+use crate::{Result, Error, KeyStat};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A wrapper for sensitive values such as keys and decrypted plaintexts.
+/// 
+/// The inner value is overwritten with zeros when the `Secret` is dropped, and
+/// its `Debug` output is redacted so it cannot leak into logs by accident.
+/// Use [`Secret::expose`] to access the value.
+pub struct Secret<T: Zeroize>(T);
+
+/// A decrypted plaintext that is zeroized on drop.
+pub type SecretString = Secret<String>;
+/// Key material that is zeroized on drop.
+pub type SecretBytes = Secret<Vec<u8>>;
+
+impl<T: Zeroize> Secret<T> {
+    /// Wraps a sensitive value.
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns a reference to the sensitive value.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+impl<T: Zeroize + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+/// Suffix of the sidecar app that holds blind index digests for a vault app.
+pub const BLIND_INDEX_SUFFIX: &str = "::blind";
+
+/// Suffix of the sidecar app that holds secrets other personas have shared.
+pub const GRANTS_SUFFIX: &str = "::grants";
+/// Suffix of the sidecar app in which a persona records the grants it has given.
+pub const GRANTED_SUFFIX: &str = "::granted";
+/// Suffix of the sidecar app that holds per-entry vault metadata.
+pub const META_SUFFIX: &str = "::vault";
+
+/// Metadata recorded alongside every entry written through a [`VaultScope`](crate::VaultScope).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultEntryMeta {
+    /// When the entry was first written, in milliseconds since the Unix epoch.
+    pub created_at: u64,
+    /// When the entry was last written, in milliseconds since the Unix epoch.
+    pub updated_at: u64,
+    /// Identifier of the master key the entry is encrypted with, see [`key_id`].
+    /// 
+    /// Empty if the entry has no recorded metadata and does not decrypt with the reader's key.
+    pub key_id: String,
+}
+
+impl VaultEntryMeta {
+    /// Describes an entry without recorded metadata from its plain key stats.
+    pub fn unrecorded(cipher_hex: &str, stat: &KeyStat, master_key: &[u8]) -> Result<Self> {
+        let key_id = match decrypt(cipher_hex, master_key) {
+            Ok(_) => key_id(master_key)?,
+            Err(_) => String::new(),
+        };
+        Ok(Self {
+            created_at: stat.created_at.unwrap_or(0),
+            updated_at: stat.updated_at.unwrap_or(0),
+            key_id,
+        })
+    }
+}
+
+/// Returns the ID of the sidecar app holding blind index digests for `app_id`.
+pub fn blind_index_app(app_id: &str) -> String {
+    format!("{}{}", app_id, BLIND_INDEX_SUFFIX)
+}
+
+/// Returns the ID of the sidecar app holding vault metadata for `app_id`.
+pub fn meta_app(app_id: &str) -> String {
+    format!("{}{}", app_id, META_SUFFIX)
+}
+
+/// Returns the ID of the sidecar app holding grants received for `app_id`.
+pub fn grants_app(app_id: &str) -> String {
+    format!("{}{}", app_id, GRANTS_SUFFIX)
+}
+
+/// Returns the ID of the sidecar app recording the grants given for `app_id`.
+pub fn granted_app(app_id: &str) -> String {
+    format!("{}{}", app_id, GRANTED_SUFFIX)
+}
+
+/// Returns the key under which a grant of `key` from `src_persona` is stored.
+/// 
+/// The persona ID is length-prefixed, so IDs and keys containing `/` cannot collide.
+pub fn grant_key(src_persona: &str, key: &str) -> String {
+    format!("{}:{}/{}", src_persona.len(), src_persona, key)
+}
+
+/// Derives the key that opens grants of `app_id` secrets from `src_persona` to `dst_persona`.
+/// 
+/// The key is derived from the recipient's master key with HKDF-SHA256. The recipient
+/// can hand it to `src_persona` without exposing the master key or grants from anyone else.
+pub fn recipient_key(master_key: &[u8], app_id: &str, src_persona: &str, dst_persona: &str) -> Result<SecretBytes> {
+    if master_key.len() != 32 {
+        return Err(Error::Internal("Key must be 32 bytes".to_string()));
+    }
+    let info = format!("celerix-grant/{}:{}/{}:{}/{}", app_id.len(), app_id, src_persona.len(), src_persona, dst_persona);
+    let mut okm = Secret::new(vec![0u8; 32]);
+    Hkdf::<Sha256>::new(None, master_key)
+        .expand(info.as_bytes(), &mut okm.0)
+        .map_err(|e| Error::Internal(e.to_string()))?;
+    Ok(okm)
+}
+
+/// Encrypts a key under a master key, for storage in the store.
+pub fn wrap_key(key: &[u8], master_key: &[u8]) -> Result<String> {
+    Ok(hex::encode(encrypt_bytes(key, master_key)?))
+}
+
+/// Decrypts the output of [`wrap_key`].
+pub fn unwrap_key(wrapped: &str, master_key: &[u8]) -> Result<SecretBytes> {
+    let combined = hex::decode(wrapped).map_err(|e| Error::Internal(e.to_string()))?;
+    decrypt_bytes(&combined, master_key)
+}
+
+/// Returns `true` if `value` looks like the output of [`encrypt`]: a hex string
+/// long enough to hold a nonce and an authentication tag.
+pub fn is_ciphertext(value: &serde_json::Value) -> bool {
+    value.as_str().is_some_and(|s| s.len() >= 2 * (12 + 16) && s.len() % 2 == 0 && s.bytes().all(|b| b.is_ascii_hexdigit()))
+}
 
 /// Encrypts a plaintext string using AES-256-GCM and a 32-byte key.
 /// 
 /// Returns a hex-encoded string containing the nonce followed by the ciphertext.
 pub fn encrypt(plaintext: &str, key: &[u8]) -> Result<String> {
+    let combined = encrypt_bytes(plaintext.as_bytes(), key)?;
+    Ok(hex::encode(combined))
+}
+
+/// Decrypts a hex-encoded ciphertext string using AES-256-GCM and a 32-byte key.
+/// 
+/// The `cipher_hex` must be the output of [`encrypt`], containing the 12-byte
+/// nonce followed by the ciphertext.
+pub fn decrypt(cipher_hex: &str, key: &[u8]) -> Result<SecretString> {
+    let combined = hex::decode(cipher_hex).map_err(|e| Error::Internal(e.to_string()))?;
+    let plaintext_bytes = decrypt_bytes(&combined, key)?;
+    let plaintext = std::str::from_utf8(plaintext_bytes.expose()).map_err(|e| Error::Internal(e.to_string()))?;
+    Ok(Secret::new(plaintext.to_string()))
+}
+
+/// Encrypts raw bytes using AES-256-GCM and a 32-byte key.
+/// 
+/// Returns the 12-byte nonce followed by the ciphertext. This is the binary
+/// form used for at-rest encryption of persona files.
+pub fn encrypt_bytes(plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
     if key.len() != 32 {
         return Err(Error::Internal("Key must be 32 bytes".to_string()));
     }
     let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| Error::Internal(e.to_string()))?;
     let nonce = Aes256Gcm::generate_nonce(&mut OsRng); // 96 bits / 12 bytes
-    let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes()).map_err(|e| Error::Internal(e.to_string()))?;
+    let ciphertext = cipher.encrypt(&nonce, plaintext).map_err(|e| Error::Internal(e.to_string()))?;
 
     let mut combined = nonce.to_vec();
     combined.extend_from_slice(&ciphertext);
-    Ok(hex::encode(combined))
+    Ok(combined)
 }
 
-/// Decrypts a hex-encoded ciphertext string using AES-256-GCM and a 32-byte key.
-/// 
-/// The `cipher_hex` must be the output of [`encrypt`], containing the 12-byte
-/// nonce followed by the ciphertext.
-pub fn decrypt(cipher_hex: &str, key: &[u8]) -> Result<String> {
+/// Decrypts the output of [`encrypt_bytes`] using AES-256-GCM and a 32-byte key.
+pub fn decrypt_bytes(combined: &[u8], key: &[u8]) -> Result<SecretBytes> {
     if key.len() != 32 {
         return Err(Error::Internal("Key must be 32 bytes".to_string()));
     }
-    let combined = hex::decode(cipher_hex).map_err(|e| Error::Internal(e.to_string()))?;
     if combined.len() < 12 {
         return Err(Error::Internal("Ciphertext too short".to_string()));
     }
@@ -37,8 +207,42 @@ pub fn decrypt(cipher_hex: &str, key: &[u8]) -> Result<String> {
     let (nonce_bytes, ciphertext) = combined.split_at(12);
     let nonce = Nonce::from_slice(nonce_bytes);
 
-    let plaintext_bytes = cipher.decrypt(nonce, ciphertext).map_err(|_| Error::Internal("decryption failed (wrong key or tampered data)".to_string()))?;
-    String::from_utf8(plaintext_bytes).map_err(|e| Error::Internal(e.to_string()))
+    let plaintext = cipher.decrypt(nonce, ciphertext).map_err(|_| Error::Internal("decryption failed (wrong key or tampered data)".to_string()))?;
+    Ok(Secret::new(plaintext))
+}
+
+/// Derives a short, non-secret identifier for a master key.
+/// 
+/// Entries encrypted with the same key share the same ID, which makes key
+/// rotation and mismatched keys easy to spot without exposing the key itself.
+pub fn key_id(key: &[u8]) -> Result<String> {
+    if key.len() != 32 {
+        return Err(Error::Internal("Key must be 32 bytes".to_string()));
+    }
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).map_err(|e| Error::Internal(e.to_string()))?;
+    mac.update(b"celerix-key-id");
+    Ok(hex::encode(&mac.finalize().into_bytes()[..8]))
+}
+
+/// Computes the blind index digest of a plaintext under a vault master key.
+/// 
+/// The digest is a hex-encoded HMAC-SHA256 keyed with a subkey derived from
+/// `key`, so equal plaintexts produce equal digests while the digest itself
+/// reveals nothing usable for decryption. Only holders of the master key can
+/// compute the digest for a given plaintext.
+pub fn blind_index(plaintext: &str, key: &[u8]) -> Result<String> {
+    if key.len() != 32 {
+        return Err(Error::Internal("Key must be 32 bytes".to_string()));
+    }
+    let mut kdf = <HmacSha256 as Mac>::new_from_slice(key).map_err(|e| Error::Internal(e.to_string()))?;
+    kdf.update(b"celerix-blind-index");
+    let mut derived = kdf.finalize().into_bytes();
+    let index_key = Secret::new(derived.to_vec());
+    derived.as_mut_slice().zeroize();
+
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(index_key.expose()).map_err(|e| Error::Internal(e.to_string()))?;
+    mac.update(plaintext.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
 }
 
 #[cfg(test)]
@@ -52,7 +256,7 @@ mod tests {
         let ciphertext = encrypt(plaintext, key).unwrap();
         assert_ne!(ciphertext, plaintext);
         let decrypted = decrypt(&ciphertext, key).unwrap();
-        assert_eq!(decrypted, plaintext);
+        assert_eq!(decrypted.expose(), plaintext);
     }
 
     #[test]
@@ -63,4 +267,36 @@ mod tests {
         let ciphertext = encrypt(plaintext, key1).unwrap();
         assert!(decrypt(&ciphertext, key2).is_err());
     }
+
+    #[test]
+    fn test_secret_debug_is_redacted() {
+        let secret = SecretString::new("hunter2".to_string());
+        assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+        assert_eq!(secret.expose(), "hunter2");
+    }
+
+    #[test]
+    fn test_blind_index_is_deterministic_per_key() {
+        let key1 = b"thisis32byteslongsecretkey123456";
+        let key2 = b"another32byteslongsecretkey65432";
+        let digest = blind_index("alice@example.com", key1).unwrap();
+        assert_eq!(digest, blind_index("alice@example.com", key1).unwrap());
+        assert_ne!(digest, blind_index("bob@example.com", key1).unwrap());
+        assert_ne!(digest, blind_index("alice@example.com", key2).unwrap());
+        assert!(!digest.contains("alice"));
+    }
+
+    #[test]
+    fn test_grant_keys() {
+        assert_ne!(grant_key("a/b", "c"), grant_key("a", "b/c"));
+
+        let master = b"thisis32byteslongsecretkey123456";
+        let key = recipient_key(master, "shared", "alice", "bob").unwrap();
+        assert_eq!(key.expose(), recipient_key(master, "shared", "alice", "bob").unwrap().expose());
+        assert_ne!(key.expose(), recipient_key(master, "shared", "carol", "bob").unwrap().expose());
+        assert_ne!(key.expose().as_slice(), master.as_slice());
+
+        let wrapped = wrap_key(key.expose(), master).unwrap();
+        assert_eq!(unwrap_key(&wrapped, master).unwrap().expose(), key.expose());
+    }
 }
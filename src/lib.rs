@@ -13,10 +13,12 @@ pub mod engine;
 pub mod sdk;
 pub mod server;
 
-use serde_json;
 use thiserror::Error;
 use async_trait::async_trait;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use engine::vault::{SecretBytes, SecretString, VaultEntryMeta};
 
 /// Errors returned by the Celerix Store.
 #[derive(Error, Debug)]
@@ -30,6 +32,41 @@ pub enum Error {
     /// The requested key does not exist within the app.
     #[error("key not found")]
     KeyNotFound,
+    /// The JSON pointer does not resolve to a field within the value.
+    #[error("path not found")]
+    PathNotFound,
+    /// The stored value has the wrong type for the requested operation.
+    #[error("wrong type: {0}")]
+    WrongType(String),
+    /// The result of an arithmetic operation does not fit the value's type.
+    #[error("out of range: {0}")]
+    OutOfRange(String),
+    /// The queue message does not exist, was already acknowledged, or was delivered again since the receipt was issued.
+    #[error("message not found")]
+    MessageNotFound,
+    /// The lock is not held under the given fencing token, or its lease expired.
+    #[error("lock not held")]
+    LockNotHeld,
+    /// The target persona or app of a rename, copy or move already exists.
+    #[error("already exists")]
+    AlreadyExists,
+    /// The app ID contains `::`, which separates apps from the store's internal sidecar apps.
+    #[error("invalid app id: {0}")]
+    InvalidAppId(String),
+    /// The key is computed by the store, such as a derived key, and cannot be written directly.
+    #[error("read only: {0}")]
+    ReadOnly(String),
+    /// No secondary index is defined for the app and path.
+    #[error("index not found")]
+    IndexNotFound,
+    /// The value does not satisfy the JSON Schema registered for its app.
+    /// 
+    /// Each entry is a `"<json pointer>: <problem>"` pair describing one violation.
+    #[error("validation failed: {}", .0.join("; "))]
+    ValidationFailed(Vec<String>),
+    /// A JSON Patch or Merge Patch could not be applied.
+    #[error("patch failed: {0}")]
+    PatchFailed(String),
     /// An internal error occurred.
     #[error("internal error: {0}")]
     Internal(String),
@@ -45,13 +82,296 @@ pub enum Error {
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Reserved ID for global/system-level data.
+/// 
+/// It also serves as the last fallback layer of [`LayeredConfig::get_effective`].
 pub const SYSTEM_PERSONA: &str = "_system";
 
+/// A partial update applied atomically to a stored JSON value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Patch {
+    /// An RFC 6902 JSON Patch: an array of `add`, `remove`, `replace`, `move`,
+    /// `copy` and `test` operations, applied all-or-nothing.
+    Json(serde_json::Value),
+    /// An RFC 7396 JSON Merge Patch document.
+    Merge(serde_json::Value),
+}
+
+impl Patch {
+    /// Applies the patch to `target` in place.
+    /// 
+    /// On failure `target` is left unchanged.
+    pub fn apply(&self, target: &mut serde_json::Value) -> Result<()> {
+        match self {
+            Patch::Json(ops) => {
+                let ops: json_patch::Patch = serde_json::from_value(ops.clone())
+                    .map_err(|e| Error::PatchFailed(e.to_string()))?;
+                json_patch::patch(target, &ops).map_err(|e| Error::PatchFailed(e.to_string()))
+            }
+            Patch::Merge(doc) => {
+                json_patch::merge(target, doc);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A condition on a JSON value, used to filter [`BatchExporter::query`] results.
+/// 
+/// Paths are RFC 6901 JSON pointers into the value, `""` being the value itself.
+/// A path that does not resolve only matches under [`Predicate::Not`].
+/// Comparisons apply to two numbers, two strings or two booleans; other pairs never match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// The field equals `value`. Numbers compare by value, so `1` equals `1.0`.
+    Eq { path: String, value: serde_json::Value },
+    /// The field is greater than `value`.
+    Gt { path: String, value: serde_json::Value },
+    /// The field is greater than or equal to `value`.
+    Gte { path: String, value: serde_json::Value },
+    /// The field is less than `value`.
+    Lt { path: String, value: serde_json::Value },
+    /// The field is less than or equal to `value`.
+    Lte { path: String, value: serde_json::Value },
+    /// The field is present, even if `null`.
+    Exists { path: String },
+    /// The field equals one of `values`.
+    In { path: String, values: Vec<serde_json::Value> },
+    /// All of the predicates match.
+    And(Vec<Predicate>),
+    /// At least one of the predicates matches.
+    Or(Vec<Predicate>),
+    /// The predicate does not match.
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    /// Returns whether `value` satisfies the predicate.
+    pub fn matches(&self, value: &serde_json::Value) -> bool {
+        use std::cmp::Ordering;
+        let cmp = |path: &str, expected: &serde_json::Value, ok: fn(Ordering) -> bool| {
+            value.pointer(path).and_then(|v| compare_values(v, expected)).map(ok).unwrap_or(false)
+        };
+        match self {
+            Predicate::Eq { path, value: expected } => value.pointer(path).map(|v| values_equal(v, expected)).unwrap_or(false),
+            Predicate::Gt { path, value } => cmp(path, value, Ordering::is_gt),
+            Predicate::Gte { path, value } => cmp(path, value, Ordering::is_ge),
+            Predicate::Lt { path, value } => cmp(path, value, Ordering::is_lt),
+            Predicate::Lte { path, value } => cmp(path, value, Ordering::is_le),
+            Predicate::Exists { path } => value.pointer(path).is_some(),
+            Predicate::In { path, values } => value.pointer(path)
+                .map(|v| values.iter().any(|candidate| values_equal(v, candidate)))
+                .unwrap_or(false),
+            Predicate::And(all) => all.iter().all(|p| p.matches(value)),
+            Predicate::Or(any) => any.iter().any(|p| p.matches(value)),
+            Predicate::Not(inner) => !inner.matches(value),
+        }
+    }
+}
+
+fn compare_values(a: &serde_json::Value, b: &serde_json::Value) -> Option<std::cmp::Ordering> {
+    use serde_json::Value;
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => Some(x.cmp(&y)),
+            _ => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        },
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+fn values_equal(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    match (a, b) {
+        (serde_json::Value::Number(_), serde_json::Value::Number(_)) => compare_values(a, b).map(|o| o.is_eq()).unwrap_or(false),
+        _ => a == b,
+    }
+}
+
+/// A filter plus an optional projection, evaluated by [`BatchExporter::query`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Query {
+    /// Only values matching this predicate are returned. `None` matches everything.
+    pub filter: Option<Predicate>,
+    /// JSON pointers of the fields to return. Empty returns whole values.
+    /// 
+    /// Projected fields keep their nesting, and missing fields are left out. Array
+    /// elements keep their index, with `null` in the positions not selected.
+    pub fields: Vec<String>,
+}
+
+impl Query {
+    /// Returns the projected `value` if it matches the filter.
+    pub fn evaluate(&self, value: &serde_json::Value) -> Option<serde_json::Value> {
+        if !self.filter.as_ref().map(|p| p.matches(value)).unwrap_or(true) {
+            return None;
+        }
+        if self.fields.is_empty() {
+            return Some(value.clone());
+        }
+
+        let mut out = serde_json::Value::Object(serde_json::Map::new());
+        'fields: for path in &self.fields {
+            let Some(field) = value.pointer(path) else { continue };
+            if path.is_empty() {
+                return Some(value.clone());
+            }
+            let raw: Vec<&str> = path[1..].split('/').collect();
+            let mut target = &mut out;
+            for i in 0..raw.len() {
+                let token = raw[i].replace("~1", "/").replace("~0", "~");
+                // Intermediate levels mirror the source's containers, so array indexes stay array indexes
+                let fresh = if i == raw.len() - 1 {
+                    field.clone()
+                } else if value.pointer(&format!("/{}", raw[..=i].join("/"))).is_some_and(|v| v.is_array()) {
+                    serde_json::Value::Array(Vec::new())
+                } else {
+                    serde_json::Value::Object(serde_json::Map::new())
+                };
+                let slot = match target {
+                    serde_json::Value::Array(items) => {
+                        let Ok(index) = token.parse::<usize>() else { continue 'fields };
+                        if items.len() <= index {
+                            items.resize(index + 1, serde_json::Value::Null);
+                        }
+                        &mut items[index]
+                    }
+                    serde_json::Value::Object(map) => map.entry(token).or_insert(serde_json::Value::Null),
+                    _ => continue 'fields,
+                };
+                // Keep what an earlier, wider field already copied, e.g. `/tags` before `/tags/0`
+                if i == raw.len() - 1 || slot.is_null() {
+                    *slot = fresh;
+                }
+                target = slot;
+            }
+        }
+        Some(out)
+    }
+}
+
+/// A server-side aggregation over one app across all personas, run by [`BatchExporter::aggregate`].
+/// 
+/// Every group reports its number of entries; `sum`, `min` and `max` list the JSON
+/// pointers of the fields to aggregate as well.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Aggregation {
+    /// Only entries whose values match this predicate are aggregated.
+    pub filter: Option<Predicate>,
+    /// Only aggregate this key of each persona, e.g. one settings document per persona.
+    pub key: Option<String>,
+    /// JSON pointer of the field to group by. Without it, all entries form one group.
+    pub group_by: Option<String>,
+    /// Fields to sum. Non-numeric values are skipped.
+    pub sum: Vec<String>,
+    /// Fields to take the minimum of. Numbers, strings and booleans are compared within their type.
+    pub min: Vec<String>,
+    /// Fields to take the maximum of.
+    pub max: Vec<String>,
+}
+
+/// One group of an [`Aggregation`] result.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AggregateGroup {
+    /// The `group_by` value, or `None` if not grouping or the field is missing.
+    pub group: Option<serde_json::Value>,
+    /// Number of entries in the group.
+    pub count: u64,
+    /// Sum of each `sum` field, computed as floating point.
+    pub sum: BTreeMap<String, f64>,
+    /// Minimum of each `min` field that had comparable values.
+    pub min: BTreeMap<String, serde_json::Value>,
+    /// Maximum of each `max` field that had comparable values.
+    pub max: BTreeMap<String, serde_json::Value>,
+}
+
+impl Aggregation {
+    /// Aggregates `(key, value)` entries into groups, ordered by their JSON-encoded group value.
+    pub fn run<'a>(&self, entries: impl IntoIterator<Item = (&'a String, &'a serde_json::Value)>) -> Vec<AggregateGroup> {
+        use std::cmp::Ordering;
+        let mut groups: BTreeMap<String, AggregateGroup> = BTreeMap::new();
+        for (key, value) in entries {
+            if self.key.as_ref().map(|k| k != key).unwrap_or(false) {
+                continue;
+            }
+            if !self.filter.as_ref().map(|p| p.matches(value)).unwrap_or(true) {
+                continue;
+            }
+
+            let group_value = self.group_by.as_ref().and_then(|path| value.pointer(path)).cloned();
+            let encoded = group_value.as_ref().map(|v| v.to_string()).unwrap_or_default();
+            let group = groups.entry(encoded).or_insert_with(|| AggregateGroup { group: group_value, ..Default::default() });
+            group.count += 1;
+
+            for path in &self.sum {
+                if let Some(n) = value.pointer(path).and_then(|v| v.as_f64()) {
+                    *group.sum.entry(path.clone()).or_default() += n;
+                }
+            }
+            let extreme = |paths: &[String], target: &mut BTreeMap<String, serde_json::Value>, better: Ordering| {
+                for path in paths {
+                    let Some(field) = value.pointer(path) else { continue };
+                    match target.get(path) {
+                        Some(current) if compare_values(field, current) != Some(better) => {}
+                        Some(_) => { target.insert(path.clone(), field.clone()); }
+                        None if compare_values(field, field).is_some() => { target.insert(path.clone(), field.clone()); }
+                        None => {}
+                    }
+                }
+            };
+            extreme(&self.min, &mut group.min, Ordering::Less);
+            extreme(&self.max, &mut group.max, Ordering::Greater);
+        }
+        groups.into_values().collect()
+    }
+}
+
 /// Defines basic read operations for the store.
 #[async_trait]
 pub trait KVReader: Send + Sync {
     /// Retrieves a value for a specific persona, app, and key.
     async fn get(&self, persona_id: &str, app_id: &str, key: &str) -> Result<serde_json::Value>;
+    /// Retrieves the sub-document at an RFC 6901 JSON pointer (e.g. `/a/b/0`) within a value.
+    /// 
+    /// An empty pointer returns the whole value.
+    async fn get_path(&self, persona_id: &str, app_id: &str, key: &str, path: &str) -> Result<serde_json::Value>;
+    /// Retrieves several `(persona, app, key)` entries at once, in the order given.
+    /// 
+    /// The entries may span personas and apps. Missing keys, including those of a
+    /// missing persona or app, come back as `None`.
+    async fn get_many(&self, keys: &[(&str, &str, &str)]) -> Result<Vec<Option<serde_json::Value>>>;
+    /// Returns whether a key exists. A missing persona or app is not an error.
+    async fn exists(&self, persona_id: &str, app_id: &str, key: &str) -> Result<bool>;
+    /// Returns size and version information about a key without transferring its value.
+    async fn stat(&self, persona_id: &str, app_id: &str, key: &str) -> Result<KeyStat>;
+}
+
+/// Metadata about a stored key, as returned by [`KVReader::stat`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyStat {
+    /// Size of the value serialized as JSON, in bytes.
+    pub size_bytes: u64,
+    /// Number of writes that changed the value, or `0` for keys written before versions were tracked.
+    pub version: u64,
+    /// When the key was first written, in milliseconds since the Unix epoch, if known.
+    pub created_at: Option<u64>,
+    /// When the key last changed, in milliseconds since the Unix epoch, if known.
+    pub updated_at: Option<u64>,
+}
+
+/// One recorded version of a key, as returned by [`KeyHistory::history`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the version was written, in milliseconds since the Unix epoch.
+    pub at: u64,
+    /// The value written, or `null` for a deletion.
+    pub value: serde_json::Value,
+    /// Whether the key was deleted at this point rather than written.
+    #[serde(default)]
+    pub deleted: bool,
 }
 
 /// Defines basic write and delete operations for the store.
@@ -60,7 +380,135 @@ pub trait KVWriter: Send + Sync {
     /// Stores a value for a specific persona, app, and key.
     async fn set(&self, persona_id: &str, app_id: &str, key: &str, value: serde_json::Value) -> Result<()>;
     /// Deletes a key from a specific persona and app.
+    /// 
+    /// If the store keeps a trash, the key is moved there instead (see [`TrashBin`]).
     async fn delete(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()>;
+    /// Stores several `(persona, app, key, value)` entries at once.
+    /// 
+    /// The entries may span personas and apps. They are written together, all or
+    /// nothing, and each affected persona is persisted only once.
+    async fn set_many(&self, entries: Vec<(&str, &str, &str, serde_json::Value)>) -> Result<()>;
+    /// Atomically adds `delta` to an integer value and returns the new value.
+    /// 
+    /// A missing key is treated as `0`. Use a negative `delta` to decrement.
+    /// Fails with [`Error::WrongType`] if the stored value is not an integer, and
+    /// with [`Error::OutOfRange`] if the stored value or the result does not fit in an `i64`.
+    async fn incr(&self, persona_id: &str, app_id: &str, key: &str, delta: i64) -> Result<i64>;
+    /// Atomically applies a [`Patch`] to a value and returns the patched value.
+    /// 
+    /// A missing key is patched starting from `null`. If the patch fails, for example
+    /// because a JSON Patch `test` operation does not match, the value is left unchanged.
+    async fn patch(&self, persona_id: &str, app_id: &str, key: &str, patch: Patch) -> Result<serde_json::Value>;
+}
+
+/// Which end of a list an operation applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    /// The start of the array (index 0).
+    Front,
+    /// The end of the array.
+    Back,
+}
+
+/// Atomic list operations on JSON array values.
+/// 
+/// A missing key behaves like an empty list. Operating on a value that is not
+/// an array fails with [`Error::WrongType`].
+#[async_trait]
+pub trait ListOperations: Send + Sync {
+    /// Pushes a value onto one end of a list and returns the new length.
+    async fn list_push(&self, persona_id: &str, app_id: &str, key: &str, end: ListEnd, value: serde_json::Value) -> Result<usize>;
+    /// Removes and returns the value at one end of a list, or `None` if it is empty.
+    async fn list_pop(&self, persona_id: &str, app_id: &str, key: &str, end: ListEnd) -> Result<Option<serde_json::Value>>;
+    /// Returns the elements from `start` to `stop`, both inclusive.
+    /// 
+    /// Negative indexes count from the end, so `(0, -1)` returns the whole list.
+    async fn list_range(&self, persona_id: &str, app_id: &str, key: &str, start: i64, stop: i64) -> Result<Vec<serde_json::Value>>;
+    /// Returns the length of a list.
+    async fn list_len(&self, persona_id: &str, app_id: &str, key: &str) -> Result<usize>;
+}
+
+/// Atomic set operations on JSON array values holding unique members.
+/// 
+/// A missing key behaves like an empty set. Operating on a value that is not
+/// an array fails with [`Error::WrongType`].
+#[async_trait]
+pub trait SetOperations: Send + Sync {
+    /// Adds a member to a set. Returns `false` if it was already present.
+    async fn set_add(&self, persona_id: &str, app_id: &str, key: &str, member: serde_json::Value) -> Result<bool>;
+    /// Removes a member from a set. Returns `false` if it was not present.
+    async fn set_remove(&self, persona_id: &str, app_id: &str, key: &str, member: &serde_json::Value) -> Result<bool>;
+    /// Returns all members of a set.
+    async fn set_members(&self, persona_id: &str, app_id: &str, key: &str) -> Result<Vec<serde_json::Value>>;
+    /// Returns `true` if `member` is in the set.
+    async fn set_contains(&self, persona_id: &str, app_id: &str, key: &str, member: &serde_json::Value) -> Result<bool>;
+}
+
+/// A message delivered from a queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueMessage {
+    /// Queue-unique message ID.
+    pub id: u64,
+    /// The message payload.
+    pub body: serde_json::Value,
+    /// How many times the message has been delivered, including this delivery.
+    pub attempts: u32,
+    /// Opaque handle of this delivery, used to acknowledge the message.
+    /// 
+    /// It stops working once the message is delivered again, so a consumer whose
+    /// visibility timeout ran out cannot acknowledge another consumer's delivery.
+    pub receipt: String,
+}
+
+/// Durable per-app message queues with visibility timeouts and dead-lettering.
+/// 
+/// Delivery is at-least-once: a dequeued message is hidden for the visibility
+/// timeout and delivered again unless it is acknowledged in time. After too many
+/// failed deliveries a message moves to the queue's dead-letter list.
+#[async_trait]
+pub trait QueueOperations: Send + Sync {
+    /// Appends a message to a queue and returns its ID.
+    async fn enqueue(&self, persona_id: &str, app_id: &str, queue: &str, body: serde_json::Value) -> Result<u64>;
+    /// Takes the oldest visible message, hiding it from other consumers for `visibility`.
+    /// 
+    /// Waits up to `wait` for a message to become available and returns `None` if
+    /// none did.
+    async fn dequeue(&self, persona_id: &str, app_id: &str, queue: &str, visibility: Duration, wait: Duration) -> Result<Option<QueueMessage>>;
+    /// Acknowledges a delivery, identified by its [`QueueMessage::receipt`], as processed
+    /// and removes the message from the queue.
+    /// 
+    /// Fails with [`Error::MessageNotFound`] if the message is gone or was delivered again since.
+    async fn ack(&self, persona_id: &str, app_id: &str, queue: &str, receipt: &str) -> Result<()>;
+    /// Returns a delivered message to the queue for immediate redelivery.
+    async fn nack(&self, persona_id: &str, app_id: &str, queue: &str, receipt: &str) -> Result<()>;
+    /// Lists the messages that exhausted their delivery attempts.
+    async fn dead_letters(&self, persona_id: &str, app_id: &str, queue: &str) -> Result<Vec<QueueMessage>>;
+}
+
+/// A lease on a named lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Fencing token, strictly increasing with every acquisition of the same lock.
+    /// 
+    /// Pass it along with writes to shared resources so that a stale holder whose
+    /// lease already expired can be rejected.
+    pub token: u64,
+    /// When the lease ends, in milliseconds since the Unix epoch.
+    pub expires_at: u64,
+}
+
+/// Named locks with time-limited leases, for leader election and exclusive jobs.
+/// 
+/// A lease ends automatically when its TTL expires. The owner identifies itself
+/// by the lease's fencing token when renewing or releasing.
+#[async_trait]
+pub trait LockManager: Send + Sync {
+    /// Acquires a lock for `ttl`, returning `None` if another holder has a live lease.
+    async fn acquire_lock(&self, persona_id: &str, app_id: &str, name: &str, ttl: Duration) -> Result<Option<Lease>>;
+    /// Extends a held lease to `ttl` from now. Fails with [`Error::LockNotHeld`] if it expired.
+    async fn renew_lock(&self, persona_id: &str, app_id: &str, name: &str, token: u64, ttl: Duration) -> Result<Lease>;
+    /// Releases a held lock.
+    async fn release_lock(&self, persona_id: &str, app_id: &str, name: &str, token: u64) -> Result<()>;
 }
 
 /// Allows discovering personas and apps within the store.
@@ -69,7 +517,11 @@ pub trait AppEnumeration: Send + Sync {
     /// Lists all available persona IDs.
     async fn get_personas(&self) -> Result<Vec<String>>;
     /// Lists all app IDs for a given persona.
+    /// 
+    /// Internal sidecar apps, whose names contain `::`, are not included.
     async fn get_apps(&self, persona_id: &str) -> Result<Vec<String>>;
+    /// Lists the key names of an app, sorted, without their values.
+    async fn get_keys(&self, persona_id: &str, app_id: &str) -> Result<Vec<String>>;
 }
 
 /// Allows retrieving bulk data from the store.
@@ -79,13 +531,244 @@ pub trait BatchExporter: Send + Sync {
     async fn get_app_store(&self, persona_id: &str, app_id: &str) -> Result<HashMap<String, serde_json::Value>>;
     /// Returns data for a specific app across all personas.
     async fn dump_app(&self, app_id: &str) -> Result<HashMap<String, HashMap<String, serde_json::Value>>>;
+    /// Returns the key-value pairs of an app within a persona that match `query`, projected as requested.
+    async fn query(&self, persona_id: &str, app_id: &str, query: &Query) -> Result<HashMap<String, serde_json::Value>>;
+    /// Like [`BatchExporter::query`], but across all personas as in [`BatchExporter::dump_app`].
+    /// 
+    /// Personas without matches are left out.
+    async fn query_app(&self, app_id: &str, query: &Query) -> Result<HashMap<String, HashMap<String, serde_json::Value>>>;
+    /// Computes counts, sums and minimums/maximums over an app across all personas, optionally grouped by a field.
+    async fn aggregate(&self, app_id: &str, aggregation: &Aggregation) -> Result<Vec<AggregateGroup>>;
 }
 
 /// Allows searching for keys across all personas.
 #[async_trait]
 pub trait GlobalSearcher: Send + Sync {
     /// Finds a key within an app by searching all personas. Returns the value and the persona ID where it was found.
+    /// 
+    /// If several personas hold the key, the first match in [`GlobalSearcher::find_global`] order wins.
     async fn get_global(&self, app_id: &str, key: &str) -> Result<(serde_json::Value, String)>;
+    /// Finds every persona holding `key` within an app. Returns `(persona_id, value)` pairs.
+    /// 
+    /// Personas listed in `priority` come first, in that order. The rest follow sorted
+    /// by ID, except [`SYSTEM_PERSONA`], which comes last as the fallback default.
+    async fn find_global(&self, app_id: &str, key: &str, priority: &[&str]) -> Result<Vec<(String, serde_json::Value)>>;
+    /// Finds vault entries of an app whose blind index matches `digest`, across all personas.
+    /// 
+    /// The digest is computed client-side with [`engine::vault::blind_index`], so the
+    /// plaintext never reaches the store. Returns `(persona_id, key)` pairs, sorted.
+    async fn find_blind(&self, app_id: &str, digest: &str) -> Result<Vec<(String, String)>>;
+}
+
+/// Stores client-side encrypted vault entries, as written by a [`VaultScope`].
+#[async_trait]
+pub trait VaultStorage: Send + Sync {
+    /// Stores an encrypted entry in one atomic step, together with its metadata and blind index.
+    /// 
+    /// `ciphertext` is the hex output of [`engine::vault::encrypt`] and `key_id` identifies
+    /// the master key it was encrypted with. Any other write or delete of the key drops its
+    /// metadata and blind index, so neither can go stale.
+    async fn vault_set(&self, persona_id: &str, app_id: &str, key: &str, ciphertext: &str, key_id: &str, blind_index: Option<&str>) -> Result<()>;
+}
+
+/// A value resolved through the configuration layers of a persona.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveValue {
+    /// The resolved value.
+    pub value: serde_json::Value,
+    /// The persona each field came from, keyed by JSON pointer.
+    /// 
+    /// Without merging this has a single entry for the whole value (`""`).
+    pub sources: BTreeMap<String, String>,
+}
+
+/// Resolves values through a cascade of personas, ending with [`SYSTEM_PERSONA`].
+#[async_trait]
+pub trait LayeredConfig: Send + Sync {
+    /// Sets the parent personas consulted, in order, after `persona_id`. An empty list removes them.
+    /// 
+    /// Parents may have parents of their own. The list is stored in [`SYSTEM_PERSONA`].
+    async fn set_parents(&self, persona_id: &str, parents: &[&str]) -> Result<()>;
+    /// Returns the parent personas configured for `persona_id`.
+    async fn get_parents(&self, persona_id: &str) -> Result<Vec<String>>;
+    /// Looks up a key in the persona, then its parents, then [`SYSTEM_PERSONA`].
+    /// 
+    /// Without `merge` the first layer holding the key wins. With `merge`, JSON objects
+    /// from all layers are deep-merged, more specific layers taking precedence.
+    /// Fails with [`Error::KeyNotFound`] if no layer holds the key.
+    async fn get_effective(&self, persona_id: &str, app_id: &str, key: &str, merge: bool) -> Result<EffectiveValue>;
+}
+
+/// Maintains secondary indexes on JSON fields of an app's values.
+/// 
+/// Definitions are stored in [`SYSTEM_PERSONA`]; the index entries themselves are kept
+/// in memory, updated on every write and rebuilt when the store is loaded.
+#[async_trait]
+pub trait IndexManager: Send + Sync {
+    /// Indexes the field at JSON pointer `path` for all values of `app_id`, across personas.
+    async fn create_index(&self, app_id: &str, path: &str) -> Result<()>;
+    /// Removes an index. Fails with [`Error::IndexNotFound`] if it does not exist.
+    async fn drop_index(&self, app_id: &str, path: &str) -> Result<()>;
+    /// Lists the indexed paths of an app.
+    async fn list_indexes(&self, app_id: &str) -> Result<Vec<String>>;
+    /// Returns the sorted `(persona_id, key)` pairs whose field at `path` equals `value` exactly.
+    /// 
+    /// Fails with [`Error::IndexNotFound`] if the path is not indexed.
+    async fn find_by_index(&self, app_id: &str, path: &str, value: &serde_json::Value) -> Result<Vec<(String, String)>>;
+}
+
+/// A key matching a full-text search, with its relevance score.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit {
+    /// The matching key.
+    pub key: String,
+    /// TF-IDF relevance; higher is better.
+    pub score: f64,
+}
+
+/// Full-text search over the string values of selected apps.
+/// 
+/// Every string within a value, at any depth, is split into lowercase alphanumeric terms.
+#[async_trait]
+pub trait TextSearch: Send + Sync {
+    /// Starts indexing the string values of `app_id` in all personas. The setting is stored in [`SYSTEM_PERSONA`].
+    async fn enable_search(&self, app_id: &str) -> Result<()>;
+    /// Stops indexing `app_id`.
+    async fn disable_search(&self, app_id: &str) -> Result<()>;
+    /// Returns the keys of a persona's app containing any of `terms`, best match first.
+    /// 
+    /// Fails with [`Error::IndexNotFound`] if search is not enabled for the app.
+    async fn search(&self, persona_id: &str, app_id: &str, terms: &str) -> Result<Vec<SearchHit>>;
+}
+
+/// Reads and restores past values of keys.
+/// 
+/// Every change to a key is recorded, up to the store's retention setting, alongside
+/// the persona. Deleting a key is recorded as a tombstone entry, so the history
+/// survives it. Timestamps are milliseconds since the Unix epoch.
+#[async_trait]
+pub trait KeyHistory: Send + Sync {
+    /// Returns the recorded versions of a key, newest first, including the current value.
+    /// 
+    /// Deleting a key keeps its history and records the deletion as an entry of its own.
+    async fn history(&self, persona_id: &str, app_id: &str, key: &str) -> Result<Vec<HistoryEntry>>;
+    /// Returns the value a key had at `timestamp`.
+    /// 
+    /// Fails with [`Error::KeyNotFound`] if the key did not exist then, or that version is no longer retained.
+    async fn get_at(&self, persona_id: &str, app_id: &str, key: &str, timestamp: u64) -> Result<serde_json::Value>;
+    /// Restores a key to the value it had at `timestamp`, which also brings back a deleted key.
+    /// 
+    /// The restore is itself recorded as a new version. Fails with [`Error::KeyNotFound`]
+    /// if no version at or before `timestamp` is retained, or the key was deleted then.
+    async fn revert(&self, persona_id: &str, app_id: &str, key: &str, timestamp: u64) -> Result<()>;
+}
+
+/// A soft-deleted key, as returned by [`TrashBin::list_trash`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// The app the key was deleted from.
+    pub app_id: String,
+    /// The deleted key.
+    pub key: String,
+    /// The value at the time of deletion.
+    pub value: serde_json::Value,
+    /// When the key was deleted, in milliseconds since the Unix epoch.
+    pub deleted_at: u64,
+    /// When the entry will be purged, in milliseconds since the Unix epoch.
+    pub expires_at: u64,
+}
+
+/// Recovers keys removed by [`KVWriter::delete`] while the store keeps a trash.
+/// 
+/// With a trash retention configured, deleted keys are moved to a per-persona trash
+/// instead of being dropped, and purged once the retention period has passed. Deleting
+/// whole apps or personas bypasses the trash.
+#[async_trait]
+pub trait TrashBin: Send + Sync {
+    /// Lists the keys in a persona's trash, most recently deleted first.
+    async fn list_trash(&self, persona_id: &str) -> Result<Vec<TrashEntry>>;
+    /// Moves a key from the trash back into its app.
+    /// 
+    /// Fails with [`Error::KeyNotFound`] if it is not in the trash, or with
+    /// [`Error::AlreadyExists`] if the key has been written again since.
+    async fn restore(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()>;
+    /// Permanently removes a key from the trash. Fails with [`Error::KeyNotFound`] if it is not there.
+    async fn purge(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()>;
+}
+
+/// Registers JSON Schemas that every value written to an app must satisfy.
+/// 
+/// Schemas are stored in [`SYSTEM_PERSONA`] and checked on every write, including
+/// patches, counters and batch writes. Values already stored are not re-checked.
+#[async_trait]
+pub trait SchemaRegistry: Send + Sync {
+    /// Sets the schema of `app_id`, replacing any previous one. Fails with [`Error::WrongType`]
+    /// if `schema` is neither an object nor a boolean, and with [`Error::ValidationFailed`]
+    /// if it uses keywords the validator does not support, rather than silently ignoring them.
+    async fn set_schema(&self, app_id: &str, schema: serde_json::Value) -> Result<()>;
+    /// Returns the schema of `app_id`, or [`Error::KeyNotFound`] if it has none.
+    async fn get_schema(&self, app_id: &str) -> Result<serde_json::Value>;
+    /// Removes the schema of `app_id`, or fails with [`Error::KeyNotFound`] if it has none.
+    async fn remove_schema(&self, app_id: &str) -> Result<()>;
+}
+
+/// Identifies a single key of a persona's app.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct KeyRef {
+    /// The persona holding the key.
+    pub persona_id: String,
+    /// The app holding the key.
+    pub app_id: String,
+    /// The key itself.
+    pub key: String,
+}
+
+impl KeyRef {
+    /// Creates a reference to a key.
+    pub fn new(persona_id: &str, app_id: &str, key: &str) -> Self {
+        Self { persona_id: persona_id.to_string(), app_id: app_id.to_string(), key: key.to_string() }
+    }
+}
+
+/// How the value of a derived key is computed from its inputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Derivation {
+    /// The number of keys in an app of a persona.
+    Count { persona_id: String, app_id: String },
+    /// JSON objects merged field by field, later sources overriding earlier ones.
+    /// 
+    /// Missing sources are skipped; without any, the derived key does not exist.
+    Merge { sources: Vec<KeyRef> },
+}
+
+/// A derived key and the computation that keeps it up to date.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DerivedKey {
+    /// Where the computed value is stored.
+    pub target: KeyRef,
+    /// How the value is computed.
+    pub derivation: Derivation,
+}
+
+/// Maintains keys whose values are computed from other keys.
+/// 
+/// Definitions are stored in [`SYSTEM_PERSONA`]. The computed value is stored at the
+/// target key and recomputed whenever an input changes, so it is read with
+/// [`KVReader::get`] like any other key. Writing or deleting a derived key directly
+/// fails with [`Error::ReadOnly`], and computed values must satisfy the target app's
+/// schema: a value that does not is logged and the derived key removed until it does.
+#[async_trait]
+pub trait DerivedKeys: Send + Sync {
+    /// Defines or replaces a derived key and computes its value right away.
+    /// 
+    /// Fails with [`Error::AlreadyExists`] if the target key holds a value that is not derived,
+    /// and with [`Error::ValidationFailed`] if the computed value violates the target app's schema.
+    async fn define_derived(&self, persona_id: &str, app_id: &str, key: &str, derivation: Derivation) -> Result<()>;
+    /// Removes a derived key along with its value. Fails with [`Error::KeyNotFound`] if it is not defined.
+    async fn drop_derived(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()>;
+    /// Lists all derived keys, ordered by target.
+    async fn list_derived(&self) -> Result<Vec<DerivedKey>>;
 }
 
 /// Handles higher-level data operations like moving keys between personas.
@@ -93,13 +776,25 @@ pub trait GlobalSearcher: Send + Sync {
 pub trait Orchestrator: Send + Sync {
     /// Moves a key from one persona to another within the same app.
     async fn move_key(&self, src_persona: &str, dst_persona: &str, app_id: &str, key: &str) -> Result<()>;
+    /// Deletes an app, including its sidecar data such as queues, locks and vault indexes.
+    async fn delete_app(&self, persona_id: &str, app_id: &str) -> Result<()>;
+    /// Deletes a persona with all of its apps, and removes its file from disk.
+    async fn delete_persona(&self, persona_id: &str) -> Result<()>;
+    /// Renames a persona. Fails with [`Error::AlreadyExists`] if `dst_persona` exists.
+    async fn rename_persona(&self, src_persona: &str, dst_persona: &str) -> Result<()>;
+    /// Copies all apps of a persona into a new persona. Fails with [`Error::AlreadyExists`] if `dst_persona` exists.
+    async fn copy_persona(&self, src_persona: &str, dst_persona: &str) -> Result<()>;
+    /// Moves an app, with its sidecar data, to another persona.
+    /// 
+    /// Fails with [`Error::AlreadyExists`] if the app already exists in `dst_persona`.
+    async fn move_app(&self, src_persona: &str, dst_persona: &str, app_id: &str) -> Result<()>;
 }
 
 /// The primary interface for interacting with the Celerix Store.
 /// 
 /// It combines all functional traits for a complete storage experience.
 #[async_trait]
-pub trait CelerixStore: KVReader + KVWriter + AppEnumeration + BatchExporter + GlobalSearcher + Orchestrator {
+pub trait CelerixStore: KVReader + KVWriter + ListOperations + SetOperations + QueueOperations + LockManager + AppEnumeration + BatchExporter + GlobalSearcher + VaultStorage + LayeredConfig + IndexManager + TextSearch + SchemaRegistry + KeyHistory + TrashBin + DerivedKeys + Orchestrator {
     /// Returns an [`AppScope`] that simplifies operations by pinning a persona and app.
     fn app(&self, persona_id: &str, app_id: &str) -> Box<dyn AppScope + '_>;
 }
@@ -109,10 +804,42 @@ pub trait CelerixStore: KVReader + KVWriter + AppEnumeration + BatchExporter + G
 pub trait AppScope: Send + Sync {
     /// Retrieves a value from the scoped app.
     async fn get(&self, key: &str) -> Result<serde_json::Value>;
+    /// Retrieves the sub-document at a JSON pointer within a value in the scoped app.
+    async fn get_path(&self, key: &str, path: &str) -> Result<serde_json::Value>;
+    /// Retrieves several keys from the scoped app at once; missing keys are `None`.
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<serde_json::Value>>>;
+    /// Returns whether a key exists in the scoped app.
+    async fn exists(&self, key: &str) -> Result<bool>;
+    /// Returns size and version information about a key in the scoped app.
+    async fn stat(&self, key: &str) -> Result<KeyStat>;
+    /// Lists the key names of the scoped app.
+    async fn keys(&self) -> Result<Vec<String>>;
     /// Stores a value in the scoped app.
     async fn set(&self, key: &str, value: serde_json::Value) -> Result<()>;
+    /// Stores several values in the scoped app at once.
+    async fn set_many(&self, entries: HashMap<String, serde_json::Value>) -> Result<()>;
     /// Deletes a key from the scoped app.
     async fn delete(&self, key: &str) -> Result<()>;
+    /// Atomically adds `delta` to an integer value in the scoped app and returns the new value.
+    async fn incr(&self, key: &str, delta: i64) -> Result<i64>;
+    /// Atomically applies a [`Patch`] to a value in the scoped app and returns the patched value.
+    async fn patch(&self, key: &str, patch: Patch) -> Result<serde_json::Value>;
+    /// Pushes a value onto one end of a list and returns the new length.
+    async fn list_push(&self, key: &str, end: ListEnd, value: serde_json::Value) -> Result<usize>;
+    /// Removes and returns the value at one end of a list, or `None` if it is empty.
+    async fn list_pop(&self, key: &str, end: ListEnd) -> Result<Option<serde_json::Value>>;
+    /// Returns the list elements from `start` to `stop`, both inclusive.
+    async fn list_range(&self, key: &str, start: i64, stop: i64) -> Result<Vec<serde_json::Value>>;
+    /// Returns the length of a list.
+    async fn list_len(&self, key: &str) -> Result<usize>;
+    /// Adds a member to a set. Returns `false` if it was already present.
+    async fn set_add(&self, key: &str, member: serde_json::Value) -> Result<bool>;
+    /// Removes a member from a set. Returns `false` if it was not present.
+    async fn set_remove(&self, key: &str, member: &serde_json::Value) -> Result<bool>;
+    /// Returns all members of a set.
+    async fn set_members(&self, key: &str) -> Result<Vec<serde_json::Value>>;
+    /// Returns `true` if `member` is in the set.
+    async fn set_contains(&self, key: &str, member: &serde_json::Value) -> Result<bool>;
     /// Returns a [`VaultScope`] for client-side encrypted storage using the provided master key.
     fn vault(&self, master_key: &[u8]) -> Box<dyn VaultScope + '_>;
 }
@@ -121,7 +848,44 @@ pub trait AppScope: Send + Sync {
 #[async_trait]
 pub trait VaultScope: Send + Sync {
     /// Retrieves and decrypts a value from the scoped app.
-    async fn get(&self, key: &str) -> Result<String>;
+    /// 
+    /// The plaintext is returned as a [`SecretString`] that is zeroized when dropped.
+    async fn get(&self, key: &str) -> Result<SecretString>;
     /// Encrypts and stores a plaintext string in the scoped app.
     async fn set(&self, key: &str, plaintext: &str) -> Result<()>;
+    /// Encrypts and stores a plaintext string, and records its blind index so it can
+    /// later be found with [`VaultScope::lookup`] or [`GlobalSearcher::find_blind`].
+    async fn set_indexed(&self, key: &str, plaintext: &str) -> Result<()>;
+    /// Deletes a vault entry together with its metadata and blind index.
+    async fn delete(&self, key: &str) -> Result<()>;
+    /// Lists the keys of all vault entries in the scoped app, sorted.
+    /// 
+    /// Vault entries are the keys holding a vault ciphertext; plain values in
+    /// the same app are ignored.
+    async fn list(&self) -> Result<Vec<String>>;
+    /// Returns `true` if `key` is a vault entry in the scoped app.
+    async fn exists(&self, key: &str) -> Result<bool>;
+    /// Returns the creation time, update time and key ID of a vault entry.
+    /// 
+    /// Entries without recorded metadata, such as those written before it was
+    /// introduced, report the times of their last plain write instead.
+    async fn metadata(&self, key: &str) -> Result<VaultEntryMeta>;
+    /// Returns the keys in the scoped app whose indexed plaintext equals `plaintext`.
+    async fn lookup(&self, plaintext: &str) -> Result<Vec<String>>;
+    /// Derives the key `src_persona` needs to share secrets of this app with the scoped persona.
+    /// 
+    /// The key only opens grants from `src_persona`, so handing it over reveals neither
+    /// this vault's master key nor secrets shared by anyone else.
+    fn recipient_key(&self, src_persona: &str) -> Result<SecretBytes>;
+    /// Shares a single secret with another persona without revealing either master key.
+    /// 
+    /// The secret is decrypted locally, re-encrypted under `recipient_key`, obtained from
+    /// [`VaultScope::recipient_key`] of `dst_persona`, and stored as a grant in the same app
+    /// of `dst_persona`, where it can be read with [`VaultScope::get_grant`]. Later writes
+    /// of the secret through this vault update the grant, and deleting it revokes the grant.
+    async fn grant(&self, key: &str, dst_persona: &str, recipient_key: &[u8]) -> Result<()>;
+    /// Removes a grant of `key` previously given to `dst_persona`.
+    async fn revoke(&self, key: &str, dst_persona: &str) -> Result<()>;
+    /// Retrieves and decrypts a secret that `src_persona` has granted to the scoped persona.
+    async fn get_grant(&self, src_persona: &str, key: &str) -> Result<SecretString>;
 }
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
+use std::time::Duration;
 use async_trait::async_trait;
 use tokio::net::TcpStream;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use crate::{Result, Error, KVReader, KVWriter, AppEnumeration, BatchExporter, GlobalSearcher, Orchestrator, CelerixStore, AppScope, VaultScope};
-use crate::engine::vault;
+use crate::{Result, Error, HistoryEntry, Patch, ListEnd, QueueMessage, Lease, KeyStat, EffectiveValue, Query, Aggregation, AggregateGroup, SearchHit, KVReader, KVWriter, VaultStorage, ListOperations, SetOperations, QueueOperations, LockManager, AppEnumeration, BatchExporter, GlobalSearcher, LayeredConfig, IndexManager, TextSearch, SchemaRegistry, KeyHistory, TrashBin, TrashEntry, DerivedKeys, Derivation, DerivedKey, Orchestrator, CelerixStore, AppScope, VaultScope};
+use crate::engine::vault::{self, SecretBytes, SecretString, VaultEntryMeta};
 use tokio::sync::Mutex;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -12,7 +13,11 @@ use serde::Serialize;
 /// 
 /// `Client` implements the [`CelerixStore`] trait and communicates with a 
 /// `celerix-stored` daemon over TCP. It features automatic reconnection and 
-/// exponential backoff retries.
+/// exponential backoff retries. Commands that are not idempotent, such as
+/// [`KVWriter::incr`], are sent at most once and never retried.
+/// 
+/// Requests share a single connection, so a blocking [`QueueOperations::dequeue`]
+/// holds up other requests on the same `Client` until it returns.
 pub struct Client {
     #[allow(dead_code)]
     addr: String,
@@ -35,15 +40,27 @@ impl Client {
     }
 
     async fn send_and_receive(&self, cmd: String) -> Result<String> {
+        self.send_with_attempts(cmd, 3).await
+    }
+
+    /// Sends a command that must not be applied twice.
+    /// 
+    /// If the connection drops after the command was written, the server may or
+    /// may not have applied it, so the error is returned instead of retrying.
+    async fn send_once(&self, cmd: String) -> Result<String> {
+        self.send_with_attempts(cmd, 1).await
+    }
+
+    async fn send_with_attempts(&self, cmd: String, attempts: u64) -> Result<String> {
         let mut inner_guard = self.inner.lock().await;
         
         // Retry logic
-        for i in 0..3 {
+        for i in 0..attempts {
             if inner_guard.is_none() {
                 match Client::connect_inner(&self.addr).await {
                     Ok(inner) => *inner_guard = Some(inner),
                     Err(e) => {
-                        if i == 2 { return Err(e); }
+                        if i == attempts - 1 { return Err(e); }
                         tokio::time::sleep(std::time::Duration::from_millis((i + 1) * 200)).await;
                         continue;
                     }
@@ -51,7 +68,7 @@ impl Client {
             }
 
             let inner = inner_guard.as_mut().unwrap();
-            if let Err(_) = inner.writer.write_all(format!("{}\n", cmd).as_bytes()).await {
+            if inner.writer.write_all(format!("{}\n", cmd).as_bytes()).await.is_err() {
                  *inner_guard = None;
                  continue;
             }
@@ -65,7 +82,7 @@ impl Client {
                 Ok(_) => {
                     let resp = resp.trim();
                     if resp.starts_with("ERR") {
-                        return Err(Error::Internal(resp[4..].to_string()));
+                        return Err(remote_error(&resp[4..]));
                     }
                     return Ok(resp.to_string());
                 }
@@ -76,7 +93,7 @@ impl Client {
             }
         }
         
-        Err(Error::Internal("failed after 3 attempts".to_string()))
+        Err(Error::Internal(format!("failed after {} attempts", attempts)))
     }
 
     async fn connect_inner(addr: &str) -> Result<ClientInner> {
@@ -96,6 +113,25 @@ impl Client {
         Ok(serde_json::from_value(val)?)
     }
 
+    /// Retrieves a type-safe value from a sub-path of a stored value.
+    /// 
+    /// Only the sub-document at the JSON pointer `path` is transferred and
+    /// deserialized into the target type.
+    pub async fn get_path_generic<T: DeserializeOwned>(&self, persona_id: &str, app_id: &str, key: &str, path: &str) -> Result<T> {
+        let val = self.get_path(persona_id, app_id, key, path).await?;
+        Ok(serde_json::from_value(val)?)
+    }
+
+    /// Acquires a lock that the server also releases when this client's connection closes.
+    /// 
+    /// This protects against holders that crash without waiting for the TTL. Note that
+    /// the client reconnects transparently after network errors, which silently ends
+    /// the lease; check [`LockManager::renew_lock`] before relying on it.
+    pub async fn acquire_session_lock(&self, persona_id: &str, app_id: &str, name: &str, ttl: Duration) -> Result<Option<Lease>> {
+        let resp = self.send_once(format!("LOCK {} {} {} {} SESSION", persona_id, app_id, name, ttl.as_millis())).await?;
+        parse_lease_response(&resp)
+    }
+
     /// Stores a type-safe value using generics.
     /// 
     /// Automatically handles JSON serialization of the value.
@@ -105,6 +141,48 @@ impl Client {
     }
 }
 
+/// Parses a `LOCK` response, where a bare `OK` means the lock is held by someone else.
+fn parse_lease_response(resp: &str) -> Result<Option<Lease>> {
+    match resp.strip_prefix("OK ") {
+        Some(json_data) => Ok(Some(serde_json::from_str(json_data)?)),
+        None if resp == "OK" => Ok(None),
+        None => Err(Error::Internal("Invalid response".to_string())),
+    }
+}
+
+/// Maps an `ERR` message from the daemon back to the matching [`Error`] variant.
+fn remote_error(msg: &str) -> Error {
+    if let Some(detail) = msg.strip_prefix("wrong type: ") {
+        return Error::WrongType(detail.to_string());
+    }
+    if let Some(detail) = msg.strip_prefix("out of range: ") {
+        return Error::OutOfRange(detail.to_string());
+    }
+    if let Some(detail) = msg.strip_prefix("invalid app id: ") {
+        return Error::InvalidAppId(detail.to_string());
+    }
+    if let Some(detail) = msg.strip_prefix("read only: ") {
+        return Error::ReadOnly(detail.to_string());
+    }
+    if let Some(detail) = msg.strip_prefix("patch failed: ") {
+        return Error::PatchFailed(detail.to_string());
+    }
+    if let Some(detail) = msg.strip_prefix("validation failed: ") {
+        return Error::ValidationFailed(detail.split("; ").map(String::from).collect());
+    }
+    match msg {
+        "persona not found" => Error::PersonaNotFound,
+        "app not found" => Error::AppNotFound,
+        "key not found" => Error::KeyNotFound,
+        "path not found" => Error::PathNotFound,
+        "message not found" => Error::MessageNotFound,
+        "lock not held" => Error::LockNotHeld,
+        "already exists" => Error::AlreadyExists,
+        "index not found" => Error::IndexNotFound,
+        _ => Error::Internal(msg.to_string()),
+    }
+}
+
 #[async_trait]
 impl KVReader for Client {
     async fn get(&self, persona_id: &str, app_id: &str, key: &str) -> Result<serde_json::Value> {
@@ -112,6 +190,37 @@ impl KVReader for Client {
         let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
         Ok(serde_json::from_str(json_data)?)
     }
+
+    async fn get_path(&self, persona_id: &str, app_id: &str, key: &str, path: &str) -> Result<serde_json::Value> {
+        // The protocol is whitespace-delimited, so the empty root pointer is a plain GET
+        if path.is_empty() {
+            return self.get(persona_id, app_id, key).await;
+        }
+        let resp = self.send_and_receive(format!("GET_PATH {} {} {} {}", persona_id, app_id, key, path)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn get_many(&self, keys: &[(&str, &str, &str)]) -> Result<Vec<Option<serde_json::Value>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let resp = self.send_and_receive(format!("MGET {}", serde_json::to_string(keys)?)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn exists(&self, persona_id: &str, app_id: &str, key: &str) -> Result<bool> {
+        let resp = self.send_and_receive(format!("EXISTS {} {} {}", persona_id, app_id, key)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn stat(&self, persona_id: &str, app_id: &str, key: &str) -> Result<KeyStat> {
+        let resp = self.send_and_receive(format!("STAT {} {} {}", persona_id, app_id, key)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
 }
 
 #[async_trait]
@@ -126,6 +235,151 @@ impl KVWriter for Client {
         self.send_and_receive(format!("DEL {} {} {}", persona_id, app_id, key)).await?;
         Ok(())
     }
+
+    async fn set_many(&self, entries: Vec<(&str, &str, &str, serde_json::Value)>) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let val_str = serde_json::to_string(&entries)?;
+        self.send_and_receive(format!("MSET {}", val_str)).await?;
+        Ok(())
+    }
+
+    async fn incr(&self, persona_id: &str, app_id: &str, key: &str, delta: i64) -> Result<i64> {
+        let resp = self.send_once(format!("INCR {} {} {} {}", persona_id, app_id, key, delta)).await?;
+        let num = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        num.parse().map_err(|_| Error::Internal("Invalid response".to_string()))
+    }
+
+    async fn patch(&self, persona_id: &str, app_id: &str, key: &str, patch: Patch) -> Result<serde_json::Value> {
+        let (kind, doc) = match &patch {
+            Patch::Json(ops) => ("JSON", ops),
+            Patch::Merge(doc) => ("MERGE", doc),
+        };
+        let doc_str = serde_json::to_string(doc)?;
+        let resp = self.send_once(format!("PATCH {} {} {} {} {}", persona_id, app_id, key, kind, doc_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+}
+
+#[async_trait]
+impl ListOperations for Client {
+    async fn list_push(&self, persona_id: &str, app_id: &str, key: &str, end: ListEnd, value: serde_json::Value) -> Result<usize> {
+        let cmd = match end { ListEnd::Front => "LPUSH", ListEnd::Back => "RPUSH" };
+        let val_str = serde_json::to_string(&value)?;
+        let resp = self.send_once(format!("{} {} {} {} {}", cmd, persona_id, app_id, key, val_str)).await?;
+        let num = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        num.parse().map_err(|_| Error::Internal("Invalid response".to_string()))
+    }
+
+    async fn list_pop(&self, persona_id: &str, app_id: &str, key: &str, end: ListEnd) -> Result<Option<serde_json::Value>> {
+        let cmd = match end { ListEnd::Front => "LPOP", ListEnd::Back => "RPOP" };
+        let resp = self.send_once(format!("{} {} {} {}", cmd, persona_id, app_id, key)).await?;
+        // A bare `OK` means the list was empty
+        match resp.strip_prefix("OK ") {
+            Some(json_data) => Ok(Some(serde_json::from_str(json_data)?)),
+            None if resp == "OK" => Ok(None),
+            None => Err(Error::Internal("Invalid response".to_string())),
+        }
+    }
+
+    async fn list_range(&self, persona_id: &str, app_id: &str, key: &str, start: i64, stop: i64) -> Result<Vec<serde_json::Value>> {
+        let resp = self.send_and_receive(format!("LRANGE {} {} {} {} {}", persona_id, app_id, key, start, stop)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn list_len(&self, persona_id: &str, app_id: &str, key: &str) -> Result<usize> {
+        let resp = self.send_and_receive(format!("LLEN {} {} {}", persona_id, app_id, key)).await?;
+        let num = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        num.parse().map_err(|_| Error::Internal("Invalid response".to_string()))
+    }
+}
+
+#[async_trait]
+impl SetOperations for Client {
+    async fn set_add(&self, persona_id: &str, app_id: &str, key: &str, member: serde_json::Value) -> Result<bool> {
+        let member_str = serde_json::to_string(&member)?;
+        let resp = self.send_and_receive(format!("SADD {} {} {} {}", persona_id, app_id, key, member_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn set_remove(&self, persona_id: &str, app_id: &str, key: &str, member: &serde_json::Value) -> Result<bool> {
+        let member_str = serde_json::to_string(member)?;
+        let resp = self.send_and_receive(format!("SREM {} {} {} {}", persona_id, app_id, key, member_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn set_members(&self, persona_id: &str, app_id: &str, key: &str) -> Result<Vec<serde_json::Value>> {
+        let resp = self.send_and_receive(format!("SMEMBERS {} {} {}", persona_id, app_id, key)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn set_contains(&self, persona_id: &str, app_id: &str, key: &str, member: &serde_json::Value) -> Result<bool> {
+        let member_str = serde_json::to_string(member)?;
+        let resp = self.send_and_receive(format!("SISMEMBER {} {} {} {}", persona_id, app_id, key, member_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+}
+
+#[async_trait]
+impl QueueOperations for Client {
+    async fn enqueue(&self, persona_id: &str, app_id: &str, queue: &str, body: serde_json::Value) -> Result<u64> {
+        let body_str = serde_json::to_string(&body)?;
+        let resp = self.send_once(format!("ENQUEUE {} {} {} {}", persona_id, app_id, queue, body_str)).await?;
+        let num = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        num.parse().map_err(|_| Error::Internal("Invalid response".to_string()))
+    }
+
+    async fn dequeue(&self, persona_id: &str, app_id: &str, queue: &str, visibility: Duration, wait: Duration) -> Result<Option<QueueMessage>> {
+        let resp = self.send_once(format!("DEQUEUE {} {} {} {} {}", persona_id, app_id, queue, visibility.as_millis(), wait.as_millis())).await?;
+        // A bare `OK` means no message became available in time
+        match resp.strip_prefix("OK ") {
+            Some(json_data) => Ok(Some(serde_json::from_str(json_data)?)),
+            None if resp == "OK" => Ok(None),
+            None => Err(Error::Internal("Invalid response".to_string())),
+        }
+    }
+
+    async fn ack(&self, persona_id: &str, app_id: &str, queue: &str, receipt: &str) -> Result<()> {
+        self.send_once(format!("ACK {} {} {} {}", persona_id, app_id, queue, receipt)).await?;
+        Ok(())
+    }
+
+    async fn nack(&self, persona_id: &str, app_id: &str, queue: &str, receipt: &str) -> Result<()> {
+        self.send_once(format!("NACK {} {} {} {}", persona_id, app_id, queue, receipt)).await?;
+        Ok(())
+    }
+
+    async fn dead_letters(&self, persona_id: &str, app_id: &str, queue: &str) -> Result<Vec<QueueMessage>> {
+        let resp = self.send_and_receive(format!("DEAD_LETTERS {} {} {}", persona_id, app_id, queue)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+}
+
+#[async_trait]
+impl LockManager for Client {
+    async fn acquire_lock(&self, persona_id: &str, app_id: &str, name: &str, ttl: Duration) -> Result<Option<Lease>> {
+        let resp = self.send_once(format!("LOCK {} {} {} {}", persona_id, app_id, name, ttl.as_millis())).await?;
+        parse_lease_response(&resp)
+    }
+
+    async fn renew_lock(&self, persona_id: &str, app_id: &str, name: &str, token: u64, ttl: Duration) -> Result<Lease> {
+        let resp = self.send_and_receive(format!("RENEW {} {} {} {} {}", persona_id, app_id, name, token, ttl.as_millis())).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn release_lock(&self, persona_id: &str, app_id: &str, name: &str, token: u64) -> Result<()> {
+        self.send_and_receive(format!("UNLOCK {} {} {} {}", persona_id, app_id, name, token)).await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -141,6 +395,12 @@ impl AppEnumeration for Client {
         let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
         Ok(serde_json::from_str(json_data)?)
     }
+
+    async fn get_keys(&self, persona_id: &str, app_id: &str) -> Result<Vec<String>> {
+        let resp = self.send_and_receive(format!("KEYS {} {}", persona_id, app_id)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
 }
 
 #[async_trait]
@@ -156,6 +416,27 @@ impl BatchExporter for Client {
         let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
         Ok(serde_json::from_str(json_data)?)
     }
+
+    async fn query(&self, persona_id: &str, app_id: &str, query: &Query) -> Result<HashMap<String, serde_json::Value>> {
+        let query_str = serde_json::to_string(query)?;
+        let resp = self.send_and_receive(format!("QUERY {} {} {}", persona_id, app_id, query_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn query_app(&self, app_id: &str, query: &Query) -> Result<HashMap<String, HashMap<String, serde_json::Value>>> {
+        let query_str = serde_json::to_string(query)?;
+        let resp = self.send_and_receive(format!("QUERY_APP {} {}", app_id, query_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn aggregate(&self, app_id: &str, aggregation: &Aggregation) -> Result<Vec<AggregateGroup>> {
+        let agg_str = serde_json::to_string(aggregation)?;
+        let resp = self.send_and_receive(format!("AGGREGATE {} {}", app_id, agg_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
 }
 
 #[async_trait]
@@ -168,6 +449,207 @@ impl GlobalSearcher for Client {
         let value = out["value"].clone();
         Ok((value, persona))
     }
+
+    async fn find_global(&self, app_id: &str, key: &str, priority: &[&str]) -> Result<Vec<(String, serde_json::Value)>> {
+        let mut cmd = format!("FIND_GLOBAL {} {}", app_id, key);
+        for persona_id in priority {
+            cmd.push(' ');
+            cmd.push_str(persona_id);
+        }
+        let resp = self.send_and_receive(cmd).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        let out: Vec<serde_json::Value> = serde_json::from_str(json_data)?;
+        out.into_iter()
+            .map(|mut m| {
+                let persona = m["persona"].as_str().ok_or_else(|| Error::Internal("Missing persona".to_string()))?.to_string();
+                Ok((persona, m["value"].take()))
+            })
+            .collect()
+    }
+
+    async fn find_blind(&self, app_id: &str, digest: &str) -> Result<Vec<(String, String)>> {
+        let resp = self.send_and_receive(format!("FIND_BLIND {} {}", app_id, digest)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        let out: Vec<serde_json::Value> = serde_json::from_str(json_data)?;
+        out.iter()
+            .map(|m| {
+                let persona = m["persona"].as_str().ok_or_else(|| Error::Internal("Missing persona".to_string()))?;
+                let key = m["key"].as_str().ok_or_else(|| Error::Internal("Missing key".to_string()))?;
+                Ok((persona.to_string(), key.to_string()))
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl VaultStorage for Client {
+    async fn vault_set(&self, persona_id: &str, app_id: &str, key: &str, ciphertext: &str, key_id: &str, blind_index: Option<&str>) -> Result<()> {
+        let mut cmd = format!("VAULT_SET {} {} {} {} {}", persona_id, app_id, key, ciphertext, key_id);
+        if let Some(digest) = blind_index {
+            cmd = format!("{} {}", cmd, digest);
+        }
+        self.send_and_receive(cmd).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LayeredConfig for Client {
+    async fn set_parents(&self, persona_id: &str, parents: &[&str]) -> Result<()> {
+        let mut cmd = format!("SET_PARENTS {}", persona_id);
+        for parent in parents {
+            cmd.push(' ');
+            cmd.push_str(parent);
+        }
+        self.send_and_receive(cmd).await?;
+        Ok(())
+    }
+
+    async fn get_parents(&self, persona_id: &str) -> Result<Vec<String>> {
+        let resp = self.send_and_receive(format!("PARENTS {}", persona_id)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn get_effective(&self, persona_id: &str, app_id: &str, key: &str, merge: bool) -> Result<EffectiveValue> {
+        let mode = if merge { " MERGE" } else { "" };
+        let resp = self.send_and_receive(format!("GET_EFFECTIVE {} {} {}{}", persona_id, app_id, key, mode)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+}
+
+#[async_trait]
+impl IndexManager for Client {
+    async fn create_index(&self, app_id: &str, path: &str) -> Result<()> {
+        self.send_and_receive(format!("CREATE_INDEX {} {}", app_id, path)).await?;
+        Ok(())
+    }
+
+    async fn drop_index(&self, app_id: &str, path: &str) -> Result<()> {
+        self.send_once(format!("DROP_INDEX {} {}", app_id, path)).await?;
+        Ok(())
+    }
+
+    async fn list_indexes(&self, app_id: &str) -> Result<Vec<String>> {
+        let resp = self.send_and_receive(format!("LIST_INDEXES {}", app_id)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn find_by_index(&self, app_id: &str, path: &str, value: &serde_json::Value) -> Result<Vec<(String, String)>> {
+        let val_str = serde_json::to_string(value)?;
+        let resp = self.send_and_receive(format!("FIND_INDEX {} {} {}", app_id, path, val_str)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        let out: Vec<serde_json::Value> = serde_json::from_str(json_data)?;
+        out.iter()
+            .map(|m| {
+                let persona = m["persona"].as_str().ok_or_else(|| Error::Internal("Missing persona".to_string()))?;
+                let key = m["key"].as_str().ok_or_else(|| Error::Internal("Missing key".to_string()))?;
+                Ok((persona.to_string(), key.to_string()))
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl TextSearch for Client {
+    async fn enable_search(&self, app_id: &str) -> Result<()> {
+        self.send_and_receive(format!("ENABLE_SEARCH {}", app_id)).await?;
+        Ok(())
+    }
+
+    async fn disable_search(&self, app_id: &str) -> Result<()> {
+        self.send_and_receive(format!("DISABLE_SEARCH {}", app_id)).await?;
+        Ok(())
+    }
+
+    async fn search(&self, persona_id: &str, app_id: &str, terms: &str) -> Result<Vec<SearchHit>> {
+        // Terms are re-tokenized by the server, so line breaks and quotes carry no meaning
+        let terms = terms.replace(['\n', '\r', '"'], " ");
+        let resp = self.send_and_receive(format!("SEARCH {} {} \"{}\"", persona_id, app_id, terms.trim())).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+}
+
+#[async_trait]
+impl KeyHistory for Client {
+    async fn history(&self, persona_id: &str, app_id: &str, key: &str) -> Result<Vec<HistoryEntry>> {
+        let resp = self.send_and_receive(format!("HISTORY {} {} {}", persona_id, app_id, key)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn get_at(&self, persona_id: &str, app_id: &str, key: &str, timestamp: u64) -> Result<serde_json::Value> {
+        let resp = self.send_and_receive(format!("GET_AT {} {} {} {}", persona_id, app_id, key, timestamp)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn revert(&self, persona_id: &str, app_id: &str, key: &str, timestamp: u64) -> Result<()> {
+        self.send_and_receive(format!("REVERT {} {} {} {}", persona_id, app_id, key, timestamp)).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TrashBin for Client {
+    async fn list_trash(&self, persona_id: &str) -> Result<Vec<TrashEntry>> {
+        let resp = self.send_and_receive(format!("TRASH {}", persona_id)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn restore(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        self.send_once(format!("RESTORE {} {} {}", persona_id, app_id, key)).await?;
+        Ok(())
+    }
+
+    async fn purge(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        self.send_once(format!("PURGE {} {} {}", persona_id, app_id, key)).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DerivedKeys for Client {
+    async fn define_derived(&self, persona_id: &str, app_id: &str, key: &str, derivation: Derivation) -> Result<()> {
+        let val_str = serde_json::to_string(&derivation)?;
+        self.send_and_receive(format!("DEFINE_DERIVED {} {} {} {}", persona_id, app_id, key, val_str)).await?;
+        Ok(())
+    }
+
+    async fn drop_derived(&self, persona_id: &str, app_id: &str, key: &str) -> Result<()> {
+        self.send_once(format!("DROP_DERIVED {} {} {}", persona_id, app_id, key)).await?;
+        Ok(())
+    }
+
+    async fn list_derived(&self) -> Result<Vec<DerivedKey>> {
+        let resp = self.send_and_receive("LIST_DERIVED".to_string()).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+}
+
+#[async_trait]
+impl SchemaRegistry for Client {
+    async fn set_schema(&self, app_id: &str, schema: serde_json::Value) -> Result<()> {
+        let val_str = serde_json::to_string(&schema)?;
+        self.send_and_receive(format!("SET_SCHEMA {} {}", app_id, val_str)).await?;
+        Ok(())
+    }
+
+    async fn get_schema(&self, app_id: &str) -> Result<serde_json::Value> {
+        let resp = self.send_and_receive(format!("GET_SCHEMA {}", app_id)).await?;
+        let json_data = resp.strip_prefix("OK ").ok_or_else(|| Error::Internal("Invalid response".to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    async fn remove_schema(&self, app_id: &str) -> Result<()> {
+        self.send_and_receive(format!("DEL_SCHEMA {}", app_id)).await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -176,6 +658,31 @@ impl Orchestrator for Client {
         self.send_and_receive(format!("MOVE {} {} {} {}", src_persona, dst_persona, app_id, key)).await?;
         Ok(())
     }
+
+    async fn delete_app(&self, persona_id: &str, app_id: &str) -> Result<()> {
+        self.send_once(format!("DEL_APP {} {}", persona_id, app_id)).await?;
+        Ok(())
+    }
+
+    async fn delete_persona(&self, persona_id: &str) -> Result<()> {
+        self.send_once(format!("DEL_PERSONA {}", persona_id)).await?;
+        Ok(())
+    }
+
+    async fn rename_persona(&self, src_persona: &str, dst_persona: &str) -> Result<()> {
+        self.send_once(format!("RENAME_PERSONA {} {}", src_persona, dst_persona)).await?;
+        Ok(())
+    }
+
+    async fn copy_persona(&self, src_persona: &str, dst_persona: &str) -> Result<()> {
+        self.send_once(format!("COPY_PERSONA {} {}", src_persona, dst_persona)).await?;
+        Ok(())
+    }
+
+    async fn move_app(&self, src_persona: &str, dst_persona: &str, app_id: &str) -> Result<()> {
+        self.send_once(format!("MOVE_APP {} {} {}", src_persona, dst_persona, app_id)).await?;
+        Ok(())
+    }
 }
 
 impl CelerixStore for Client {
@@ -200,37 +707,239 @@ impl<'a> AppScope for RemoteAppScope<'a> {
         self.client.get(&self.persona_id, &self.app_id, key).await
     }
 
+    async fn get_path(&self, key: &str, path: &str) -> Result<serde_json::Value> {
+        self.client.get_path(&self.persona_id, &self.app_id, key, path).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<serde_json::Value>>> {
+        let keys: Vec<_> = keys.iter().map(|k| (self.persona_id.as_str(), self.app_id.as_str(), *k)).collect();
+        self.client.get_many(&keys).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        self.client.exists(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn stat(&self, key: &str) -> Result<KeyStat> {
+        self.client.stat(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.client.get_keys(&self.persona_id, &self.app_id).await
+    }
+
     async fn set(&self, key: &str, value: serde_json::Value) -> Result<()> {
         self.client.set(&self.persona_id, &self.app_id, key, value).await
     }
 
+    async fn set_many(&self, entries: HashMap<String, serde_json::Value>) -> Result<()> {
+        let entries = entries.iter()
+            .map(|(k, v)| (self.persona_id.as_str(), self.app_id.as_str(), k.as_str(), v.clone()))
+            .collect();
+        self.client.set_many(entries).await
+    }
+
     async fn delete(&self, key: &str) -> Result<()> {
         self.client.delete(&self.persona_id, &self.app_id, key).await
     }
 
+    async fn incr(&self, key: &str, delta: i64) -> Result<i64> {
+        self.client.incr(&self.persona_id, &self.app_id, key, delta).await
+    }
+
+    async fn patch(&self, key: &str, patch: Patch) -> Result<serde_json::Value> {
+        self.client.patch(&self.persona_id, &self.app_id, key, patch).await
+    }
+
+    async fn list_push(&self, key: &str, end: ListEnd, value: serde_json::Value) -> Result<usize> {
+        self.client.list_push(&self.persona_id, &self.app_id, key, end, value).await
+    }
+
+    async fn list_pop(&self, key: &str, end: ListEnd) -> Result<Option<serde_json::Value>> {
+        self.client.list_pop(&self.persona_id, &self.app_id, key, end).await
+    }
+
+    async fn list_range(&self, key: &str, start: i64, stop: i64) -> Result<Vec<serde_json::Value>> {
+        self.client.list_range(&self.persona_id, &self.app_id, key, start, stop).await
+    }
+
+    async fn list_len(&self, key: &str) -> Result<usize> {
+        self.client.list_len(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn set_add(&self, key: &str, member: serde_json::Value) -> Result<bool> {
+        self.client.set_add(&self.persona_id, &self.app_id, key, member).await
+    }
+
+    async fn set_remove(&self, key: &str, member: &serde_json::Value) -> Result<bool> {
+        self.client.set_remove(&self.persona_id, &self.app_id, key, member).await
+    }
+
+    async fn set_members(&self, key: &str) -> Result<Vec<serde_json::Value>> {
+        self.client.set_members(&self.persona_id, &self.app_id, key).await
+    }
+
+    async fn set_contains(&self, key: &str, member: &serde_json::Value) -> Result<bool> {
+        self.client.set_contains(&self.persona_id, &self.app_id, key, member).await
+    }
+
     fn vault(&self, master_key: &[u8]) -> Box<dyn VaultScope + '_> {
         Box::new(RemoteVaultScope {
             app: self,
-            master_key: master_key.to_vec(),
+            master_key: SecretBytes::new(master_key.to_vec()),
         })
     }
 }
 
 pub struct RemoteVaultScope<'a> {
     app: &'a RemoteAppScope<'a>,
-    master_key: Vec<u8>,
+    master_key: SecretBytes,
+}
+
+impl<'a> RemoteVaultScope<'a> {
+    /// Encrypts and stores an entry, with its blind index if `indexed` is set.
+    async fn write_entry(&self, key: &str, plaintext: &str, indexed: bool) -> Result<()> {
+        let cipher_hex = vault::encrypt(plaintext, self.master_key.expose())?;
+        let key_id = vault::key_id(self.master_key.expose())?;
+        let digest = if indexed { Some(vault::blind_index(plaintext, self.master_key.expose())?) } else { None };
+        self.app.client.vault_set(&self.app.persona_id, &self.app.app_id, key, &cipher_hex, &key_id, digest.as_deref()).await?;
+        self.refresh_grants(key, plaintext).await
+    }
+
+    /// Records the grants of `key` given to other personas, keyed by recipient with the wrapped recipient key.
+    async fn granted(&self, key: &str) -> Result<serde_json::Map<String, serde_json::Value>> {
+        match self.app.client.get(&self.app.persona_id, &vault::granted_app(&self.app.app_id), key).await {
+            Ok(serde_json::Value::Object(granted)) => Ok(granted),
+            Ok(_) | Err(Error::PersonaNotFound) | Err(Error::AppNotFound) | Err(Error::KeyNotFound) => Ok(serde_json::Map::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores `plaintext` as a grant of `key` to `dst_persona`, encrypted under its recipient key.
+    async fn write_grant(&self, key: &str, dst_persona: &str, plaintext: &str, recipient_key: &[u8]) -> Result<()> {
+        let cipher_hex = vault::encrypt(plaintext, recipient_key)?;
+        let grant_key = vault::grant_key(&self.app.persona_id, key);
+        self.app.client.set(dst_persona, &vault::grants_app(&self.app.app_id), &grant_key, serde_json::Value::String(cipher_hex)).await
+    }
+
+    /// Re-encrypts the grants of `key` after its secret changed.
+    async fn refresh_grants(&self, key: &str, plaintext: &str) -> Result<()> {
+        for (dst_persona, wrapped) in self.granted(key).await? {
+            let wrapped = wrapped.as_str().ok_or_else(|| Error::Internal("Vault data is not a string".to_string()))?;
+            let recipient_key = vault::unwrap_key(wrapped, self.master_key.expose())?;
+            self.write_grant(key, &dst_persona, plaintext, recipient_key.expose()).await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl<'a> VaultScope for RemoteVaultScope<'a> {
-    async fn get(&self, key: &str) -> Result<String> {
+    async fn get(&self, key: &str) -> Result<SecretString> {
         let val = self.app.get(key).await?;
         let cipher_hex = val.as_str().ok_or_else(|| Error::Internal("Vault data is not a string".to_string()))?;
-        vault::decrypt(cipher_hex, &self.master_key)
+        vault::decrypt(cipher_hex, self.master_key.expose())
     }
 
     async fn set(&self, key: &str, plaintext: &str) -> Result<()> {
-        let cipher_hex = vault::encrypt(plaintext, &self.master_key)?;
-        self.app.set(key, serde_json::Value::String(cipher_hex)).await
+        self.write_entry(key, plaintext, false).await
+    }
+
+    async fn set_indexed(&self, key: &str, plaintext: &str) -> Result<()> {
+        self.write_entry(key, plaintext, true).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        // The store drops the entry's metadata and blind index along with it
+        self.app.delete(key).await?;
+        for dst_persona in self.granted(key).await?.keys() {
+            self.revoke(key, dst_persona).await?;
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let entries = match self.app.client.get_app_store(&self.app.persona_id, &self.app.app_id).await {
+            Ok(entries) => entries,
+            Err(Error::PersonaNotFound) | Err(Error::AppNotFound) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut keys: Vec<String> = entries.into_iter()
+            .filter(|(_, val)| vault::is_ciphertext(val))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self.app.get(key).await {
+            Ok(val) => Ok(vault::is_ciphertext(&val)),
+            Err(Error::PersonaNotFound) | Err(Error::AppNotFound) | Err(Error::KeyNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn metadata(&self, key: &str) -> Result<VaultEntryMeta> {
+        let val = self.app.get(key).await?;
+        let cipher_hex = val.as_str().filter(|_| vault::is_ciphertext(&val)).ok_or(Error::KeyNotFound)?;
+        match self.app.client.get(&self.app.persona_id, &vault::meta_app(&self.app.app_id), key).await {
+            Ok(meta) => Ok(serde_json::from_value(meta)?),
+            Err(Error::AppNotFound) | Err(Error::KeyNotFound) => {
+                VaultEntryMeta::unrecorded(cipher_hex, &self.app.stat(key).await?, self.master_key.expose())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn lookup(&self, plaintext: &str) -> Result<Vec<String>> {
+        let digest = vault::blind_index(plaintext, self.master_key.expose())?;
+        let index = match self.app.client.get_app_store(&self.app.persona_id, &vault::blind_index_app(&self.app.app_id)).await {
+            Ok(index) => index,
+            Err(Error::PersonaNotFound) | Err(Error::AppNotFound) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut keys: Vec<String> = index.into_iter()
+            .filter(|(_, val)| val.as_str() == Some(digest.as_str()))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn recipient_key(&self, src_persona: &str) -> Result<SecretBytes> {
+        vault::recipient_key(self.master_key.expose(), &self.app.app_id, src_persona, &self.app.persona_id)
+    }
+
+    async fn grant(&self, key: &str, dst_persona: &str, recipient_key: &[u8]) -> Result<()> {
+        let plaintext = self.get(key).await?;
+        self.write_grant(key, dst_persona, plaintext.expose(), recipient_key).await?;
+        let wrapped = vault::wrap_key(recipient_key, self.master_key.expose())?;
+        let record = Patch::Merge(serde_json::json!({ dst_persona: wrapped }));
+        self.app.client.patch(&self.app.persona_id, &vault::granted_app(&self.app.app_id), key, record).await?;
+        Ok(())
+    }
+
+    async fn revoke(&self, key: &str, dst_persona: &str) -> Result<()> {
+        let grant_key = vault::grant_key(&self.app.persona_id, key);
+        self.app.client.delete(dst_persona, &vault::grants_app(&self.app.app_id), &grant_key).await?;
+        let mut granted = self.granted(key).await?;
+        if granted.remove(dst_persona).is_some() {
+            let granted_app = vault::granted_app(&self.app.app_id);
+            if granted.is_empty() {
+                self.app.client.delete(&self.app.persona_id, &granted_app, key).await?;
+            } else {
+                self.app.client.set(&self.app.persona_id, &granted_app, key, serde_json::Value::Object(granted)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_grant(&self, src_persona: &str, key: &str) -> Result<SecretString> {
+        let grant_key = vault::grant_key(src_persona, key);
+        let val = self.app.client.get(&self.app.persona_id, &vault::grants_app(&self.app.app_id), &grant_key).await?;
+        let cipher_hex = val.as_str().ok_or_else(|| Error::Internal("Vault data is not a string".to_string()))?;
+        let recipient_key = self.recipient_key(src_persona)?;
+        vault::decrypt(cipher_hex, recipient_key.expose())
     }
 }
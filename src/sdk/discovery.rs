@@ -1,7 +1,7 @@
 use std::env;
 use std::sync::Arc;
 use crate::{CelerixStore, Result};
-use crate::engine::{MemStore, Persistence};
+use crate::engine::{MemStore, Persistence, persistence, history::HistoryRetention, trash};
 use crate::sdk::Client;
 
 /// Initializes a [`CelerixStore`] based on the environment.
@@ -12,7 +12,11 @@ use crate::sdk::Client;
 /// 1. If `CELERIX_STORE_ADDR` environment variable is set, it attempts to 
 ///    connect to that address in **Remote Mode**.
 /// 2. Otherwise, it initializes a [`MemStore`] with [`Persistence`] in the 
-///    specified `data_dir` in **Embedded Mode**.
+///    specified `data_dir` in **Embedded Mode**. If `CELERIX_DATA_KEY` or
+///    `CELERIX_DATA_KEY_FILE` is set, persona files are encrypted at rest.
+///    `CELERIX_HISTORY_VERSIONS`, `CELERIX_HISTORY_WINDOW_SECS` and
+///    `CELERIX_TRASH_RETENTION_SECS` turn on key history and the trash, as they
+///    do for `celerix-stored`.
 /// 
 /// # Examples
 /// 
@@ -41,8 +45,16 @@ pub async fn new(data_dir: &str) -> Result<Arc<dyn CelerixStore>> {
         }
     }
 
-    let persistence = Arc::new(Persistence::new(data_dir)?);
+    let persistence = match persistence::key_from_env()? {
+        Some(key) => Persistence::with_encryption(data_dir, key.expose())?,
+        None => Persistence::new(data_dir)?,
+    };
+    let persistence = Arc::new(persistence);
     let initial_data = persistence.load_all()?;
-    let store = MemStore::new(initial_data, Some(persistence));
+    let mut store = MemStore::new(initial_data, Some(persistence))
+        .with_history(HistoryRetention::resolve(None, None));
+    if let Some(retention) = trash::retention(None) {
+        store = store.with_trash(retention);
+    }
     Ok(Arc::new(store))
 }
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use std::time::Duration;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use log::warn;
+use crate::{Error, Result};
+use crate::engine::hooks::{AfterHook, ChangeEvent, ChangeKind};
+
+/// How long a single webhook delivery or command may take.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many events may wait for a hook before further ones are dropped.
+const HOOK_QUEUE_CAPACITY: usize = 1024;
+
+/// Returns a hook that POSTs every change event as JSON to a plain `http://` URL.
+///
+/// Events are delivered one at a time in the order they happened, by a background task
+/// of the current Tokio runtime. Failed deliveries are logged and not retried.
+pub fn webhook(url: &str) -> Result<AfterHook> {
+    let rest = url.strip_prefix("http://")
+        .ok_or_else(|| Error::Internal(format!("unsupported webhook url {}: only http:// is supported", url)))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    let host = authority.to_string();
+    let path = path.to_string();
+    let url = url.to_string();
+
+    Ok(spawn_worker(move |event| {
+        let (addr, host, path, url) = (addr.clone(), host.clone(), path.clone(), url.clone());
+        async move {
+            let body = serde_json::to_string(&event).unwrap_or_default();
+            match tokio::time::timeout(HOOK_TIMEOUT, post(&addr, &host, &path, &body)).await {
+                Ok(Ok(status)) if (200..300).contains(&status) => {}
+                Ok(Ok(status)) => warn!("Webhook {} answered with status {}", url, status),
+                Ok(Err(e)) => warn!("Webhook {} failed: {}", url, e),
+                Err(_) => warn!("Webhook {} timed out", url),
+            }
+        }
+    }))
+}
+
+/// Returns a hook that runs a shell command for every change event.
+///
+/// The event is written as JSON to the command's stdin, and also described by the
+/// `CELERIX_EVENT` (`set` or `delete`), `CELERIX_PERSONA`, `CELERIX_APP` and `CELERIX_KEY`
+/// environment variables. Commands run one at a time, in order.
+pub fn command(command: &str) -> AfterHook {
+    let command = command.to_string();
+    spawn_worker(move |event| {
+        let command = command.clone();
+        async move {
+            match tokio::time::timeout(HOOK_TIMEOUT, run(&command, &event)).await {
+                Ok(Ok(status)) if status.success() => {}
+                Ok(Ok(status)) => warn!("Hook command `{}` exited with {}", command, status),
+                Ok(Err(e)) => warn!("Hook command `{}` failed: {}", command, e),
+                Err(_) => warn!("Hook command `{}` timed out", command),
+            }
+        }
+    })
+}
+
+/// Feeds events through a channel to a task that handles them sequentially.
+///
+/// Once [`HOOK_QUEUE_CAPACITY`] events are waiting, new ones are dropped with a warning,
+/// so a slow hook cannot grow memory without bound.
+fn spawn_worker<F, Fut>(handle: F) -> AfterHook
+where
+    F: Fn(ChangeEvent) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send,
+{
+    let (tx, mut rx) = mpsc::channel::<ChangeEvent>(HOOK_QUEUE_CAPACITY);
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            handle(event).await;
+        }
+    });
+    Arc::new(move |event: &ChangeEvent| {
+        if let Err(mpsc::error::TrySendError::Full(event)) = tx.try_send(event.clone()) {
+            warn!("Hook queue is full, dropping {:?} event for {}/{}/{}", event.kind, event.persona_id, event.app_id, event.key);
+        }
+    })
+}
+
+/// Sends a minimal HTTP/1.1 POST and returns the response status code.
+async fn post(addr: &str, host: &str, path: &str, body: &str) -> std::io::Result<u16> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, host, body.len(), body
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).await?;
+    status_line.split_whitespace().nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed HTTP response"))
+}
+
+/// Runs a hook command for one event and waits for it to exit.
+async fn run(command: &str, event: &ChangeEvent) -> std::io::Result<std::process::ExitStatus> {
+    let kind = match event.kind {
+        ChangeKind::Set => "set",
+        ChangeKind::Delete => "delete",
+    };
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("CELERIX_EVENT", kind)
+        .env("CELERIX_PERSONA", &event.persona_id)
+        .env("CELERIX_APP", &event.app_id)
+        .env("CELERIX_KEY", &event.key)
+        .stdin(Stdio::piped())
+        // A command that outlives the timeout is killed rather than left running
+        .kill_on_drop(true)
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that ignores its input may exit before reading it
+        let _ = stdin.write_all(serde_json::to_string(event).unwrap_or_default().as_bytes()).await;
+    }
+    child.wait().await
+}
@@ -3,5 +3,7 @@
 /// This module provides the [`Router`] which handles incoming TCP connections
 /// and dispatches commands to the underlying store.
 pub mod router;
+/// Webhook and command hooks configured for the daemon.
+pub mod hooks;
 
 pub use router::Router;
@@ -1,7 +1,8 @@
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
-use crate::{CelerixStore, Result};
+use crate::{Aggregation, CelerixStore, Derivation, ListEnd, Patch, Query, Result};
 use log::{info, error};
 use tokio::sync::Semaphore;
 
@@ -55,11 +56,33 @@ impl Router {
     }
 }
 
+/// A lock acquired with `LOCK ... SESSION`, released when its connection closes.
+struct SessionLock {
+    persona_id: String,
+    app_id: String,
+    name: String,
+    token: u64,
+}
+
 /// Handles a single TCP connection by reading commands and writing responses.
 /// 
 /// This function is used by both the [`Router`] and the integration tests to
-/// process the Celerix Store protocol.
-pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStore>) -> Result<()> {
+/// process the Celerix Store protocol. Locks acquired in session mode are
+/// released once the connection closes, whether cleanly or with an error.
+pub async fn handle_connection(socket: TcpStream, store: Arc<dyn CelerixStore>) -> Result<()> {
+    let mut session_locks = Vec::new();
+    let res = serve(socket, &store, &mut session_locks).await;
+
+    for lock in session_locks {
+        if let Err(e) = store.release_lock(&lock.persona_id, &lock.app_id, &lock.name, lock.token).await {
+            // The lease may already have expired and been taken over
+            log::debug!("Could not release session lock {}: {}", lock.name, e);
+        }
+    }
+    res
+}
+
+async fn serve(mut socket: TcpStream, store: &Arc<dyn CelerixStore>, session_locks: &mut Vec<SessionLock>) -> Result<()> {
     let (reader, mut writer) = socket.split();
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
@@ -71,7 +94,7 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
             break;
         }
 
-        let parts: Vec<&str> = line.trim().split_whitespace().collect();
+        let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.is_empty() {
             continue;
         }
@@ -88,6 +111,16 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
                     }
                 }
             }
+            "GET_PATH" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.get_path(parts[1], parts[2], parts[3], parts[4]).await {
+                        Ok(val) => format!("OK {}", serde_json::to_string(&val)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
             "SET" => {
                 if parts.len() < 5 {
                     "ERR missing arguments".to_string()
@@ -102,6 +135,62 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
                     }
                 }
             }
+            "EXISTS" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.exists(parts[1], parts[2], parts[3]).await {
+                        Ok(found) => format!("OK {}", found),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "STAT" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.stat(parts[1], parts[2], parts[3]).await {
+                        Ok(stat) => format!("OK {}", serde_json::to_string(&stat)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "MGET" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let val_str = parts[1..].join(" ");
+                    match serde_json::from_str::<Vec<(String, String, String)>>(&val_str) {
+                        Ok(keys) => {
+                            let keys: Vec<_> = keys.iter().map(|(p, a, k)| (p.as_str(), a.as_str(), k.as_str())).collect();
+                            match store.get_many(&keys).await {
+                                Ok(values) => format!("OK {}", serde_json::to_string(&values)?),
+                                Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                            }
+                        }
+                        Err(_) => "ERR invalid json key list".to_string(),
+                    }
+                }
+            }
+            "MSET" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let val_str = parts[1..].join(" ");
+                    match serde_json::from_str::<Vec<(String, String, String, serde_json::Value)>>(&val_str) {
+                        Ok(entries) => {
+                            let entries = entries.iter()
+                                .map(|(p, a, k, v)| (p.as_str(), a.as_str(), k.as_str(), v.clone()))
+                                .collect();
+                            match store.set_many(entries).await {
+                                Ok(_) => "OK".to_string(),
+                                Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                            }
+                        }
+                        Err(_) => "ERR invalid json entry list".to_string(),
+                    }
+                }
+            }
             "DEL" => {
                 if parts.len() < 4 {
                     "ERR missing arguments".to_string()
@@ -112,6 +201,235 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
                     }
                 }
             }
+            "INCR" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match parts.get(4).map(|d| d.parse::<i64>()).unwrap_or(Ok(1)) {
+                        Ok(delta) => match store.incr(parts[1], parts[2], parts[3], delta).await {
+                            Ok(val) => format!("OK {}", val),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid delta".to_string(),
+                    }
+                }
+            }
+            "PATCH" => {
+                if parts.len() < 6 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let doc_str = parts[5..].join(" ");
+                    match serde_json::from_str(&doc_str) {
+                        Ok(doc) => {
+                            let patch = match parts[4].to_uppercase().as_str() {
+                                "JSON" => Some(Patch::Json(doc)),
+                                "MERGE" => Some(Patch::Merge(doc)),
+                                _ => None,
+                            };
+                            match patch {
+                                Some(patch) => match store.patch(parts[1], parts[2], parts[3], patch).await {
+                                    Ok(val) => format!("OK {}", serde_json::to_string(&val)?),
+                                    Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                                },
+                                None => "ERR unknown patch type".to_string(),
+                            }
+                        }
+                        Err(_) => "ERR invalid json value".to_string(),
+                    }
+                }
+            }
+            "LPUSH" | "RPUSH" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let end = if command == "LPUSH" { ListEnd::Front } else { ListEnd::Back };
+                    let val_str = parts[4..].join(" ");
+                    match serde_json::from_str(&val_str) {
+                        Ok(val) => match store.list_push(parts[1], parts[2], parts[3], end, val).await {
+                            Ok(len) => format!("OK {}", len),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid json value".to_string(),
+                    }
+                }
+            }
+            "LPOP" | "RPOP" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let end = if command == "LPOP" { ListEnd::Front } else { ListEnd::Back };
+                    match store.list_pop(parts[1], parts[2], parts[3], end).await {
+                        Ok(Some(val)) => format!("OK {}", serde_json::to_string(&val)?),
+                        Ok(None) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "LRANGE" => {
+                if parts.len() < 6 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match (parts[4].parse::<i64>(), parts[5].parse::<i64>()) {
+                        (Ok(start), Ok(stop)) => match store.list_range(parts[1], parts[2], parts[3], start, stop).await {
+                            Ok(items) => format!("OK {}", serde_json::to_string(&items)?),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        _ => "ERR invalid range".to_string(),
+                    }
+                }
+            }
+            "LLEN" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.list_len(parts[1], parts[2], parts[3]).await {
+                        Ok(len) => format!("OK {}", len),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "SADD" | "SREM" | "SISMEMBER" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let val_str = parts[4..].join(" ");
+                    match serde_json::from_str(&val_str) {
+                        Ok(member) => {
+                            let res = match command.as_str() {
+                                "SADD" => store.set_add(parts[1], parts[2], parts[3], member).await,
+                                "SREM" => store.set_remove(parts[1], parts[2], parts[3], &member).await,
+                                _ => store.set_contains(parts[1], parts[2], parts[3], &member).await,
+                            };
+                            match res {
+                                Ok(flag) => format!("OK {}", flag),
+                                Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                            }
+                        }
+                        Err(_) => "ERR invalid json value".to_string(),
+                    }
+                }
+            }
+            "SMEMBERS" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.set_members(parts[1], parts[2], parts[3]).await {
+                        Ok(members) => format!("OK {}", serde_json::to_string(&members)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "ENQUEUE" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let val_str = parts[4..].join(" ");
+                    match serde_json::from_str(&val_str) {
+                        Ok(body) => match store.enqueue(parts[1], parts[2], parts[3], body).await {
+                            Ok(id) => format!("OK {}", id),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid json value".to_string(),
+                    }
+                }
+            }
+            "DEQUEUE" => {
+                if parts.len() < 6 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match (parts[4].parse::<u64>(), parts[5].parse::<u64>()) {
+                        (Ok(visibility_ms), Ok(wait_ms)) => {
+                            let visibility = Duration::from_millis(visibility_ms);
+                            let wait = Duration::from_millis(wait_ms);
+                            match store.dequeue(parts[1], parts[2], parts[3], visibility, wait).await {
+                                Ok(Some(msg)) => format!("OK {}", serde_json::to_string(&msg)?),
+                                Ok(None) => "OK".to_string(),
+                                Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                            }
+                        }
+                        _ => "ERR invalid timeout".to_string(),
+                    }
+                }
+            }
+            "ACK" | "NACK" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let res = if command == "ACK" {
+                        store.ack(parts[1], parts[2], parts[3], parts[4]).await
+                    } else {
+                        store.nack(parts[1], parts[2], parts[3], parts[4]).await
+                    };
+                    match res {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "DEAD_LETTERS" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.dead_letters(parts[1], parts[2], parts[3]).await {
+                        Ok(msgs) => format!("OK {}", serde_json::to_string(&msgs)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "LOCK" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let session = parts.get(5).map(|m| m.eq_ignore_ascii_case("SESSION")).unwrap_or(false);
+                    match parts[4].parse::<u64>() {
+                        Ok(ttl_ms) => match store.acquire_lock(parts[1], parts[2], parts[3], Duration::from_millis(ttl_ms)).await {
+                            Ok(Some(lease)) => {
+                                if session {
+                                    session_locks.push(SessionLock {
+                                        persona_id: parts[1].to_string(),
+                                        app_id: parts[2].to_string(),
+                                        name: parts[3].to_string(),
+                                        token: lease.token,
+                                    });
+                                }
+                                format!("OK {}", serde_json::to_string(&lease)?)
+                            }
+                            Ok(None) => "OK".to_string(),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid ttl".to_string(),
+                    }
+                }
+            }
+            "RENEW" => {
+                if parts.len() < 6 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match (parts[4].parse::<u64>(), parts[5].parse::<u64>()) {
+                        (Ok(token), Ok(ttl_ms)) => match store.renew_lock(parts[1], parts[2], parts[3], token, Duration::from_millis(ttl_ms)).await {
+                            Ok(lease) => format!("OK {}", serde_json::to_string(&lease)?),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        _ => "ERR invalid arguments".to_string(),
+                    }
+                }
+            }
+            "UNLOCK" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match parts[4].parse::<u64>() {
+                        Ok(token) => match store.release_lock(parts[1], parts[2], parts[3], token).await {
+                            Ok(_) => {
+                                session_locks.retain(|l| !(l.token == token && l.name == parts[3] && l.app_id == parts[2] && l.persona_id == parts[1]));
+                                "OK".to_string()
+                            }
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid token".to_string(),
+                    }
+                }
+            }
             "LIST_PERSONAS" => {
                 match store.get_personas().await {
                     Ok(list) => format!("OK {}", serde_json::to_string(&list)?),
@@ -128,6 +446,16 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
                     }
                 }
             }
+            "KEYS" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.get_keys(parts[1], parts[2]).await {
+                        Ok(list) => format!("OK {}", serde_json::to_string(&list)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
             "DUMP" => {
                 if parts.len() < 3 {
                     "ERR missing arguments".to_string()
@@ -148,6 +476,48 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
                     }
                 }
             }
+            "QUERY" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let query_str = parts[3..].join(" ");
+                    match serde_json::from_str::<Query>(&query_str) {
+                        Ok(query) => match store.query(parts[1], parts[2], &query).await {
+                            Ok(data) => format!("OK {}", serde_json::to_string(&data)?),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid query".to_string(),
+                    }
+                }
+            }
+            "QUERY_APP" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let query_str = parts[2..].join(" ");
+                    match serde_json::from_str::<Query>(&query_str) {
+                        Ok(query) => match store.query_app(parts[1], &query).await {
+                            Ok(data) => format!("OK {}", serde_json::to_string(&data)?),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid query".to_string(),
+                    }
+                }
+            }
+            "AGGREGATE" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let agg_str = parts[2..].join(" ");
+                    match serde_json::from_str::<Aggregation>(&agg_str) {
+                        Ok(aggregation) => match store.aggregate(parts[1], &aggregation).await {
+                            Ok(groups) => format!("OK {}", serde_json::to_string(&groups)?),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid aggregation".to_string(),
+                    }
+                }
+            }
             "GET_GLOBAL" => {
                 if parts.len() < 3 {
                     "ERR missing arguments".to_string()
@@ -164,6 +534,264 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
                     }
                 }
             }
+            "FIND_GLOBAL" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.find_global(parts[1], parts[2], &parts[3..]).await {
+                        Ok(matches) => {
+                            let out: Vec<serde_json::Value> = matches.into_iter()
+                                .map(|(persona, val)| serde_json::json!({ "persona": persona, "value": val }))
+                                .collect();
+                            format!("OK {}", serde_json::to_string(&out)?)
+                        },
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "GET_EFFECTIVE" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let merge = parts.get(4).map(|m| m.eq_ignore_ascii_case("MERGE")).unwrap_or(false);
+                    match store.get_effective(parts[1], parts[2], parts[3], merge).await {
+                        Ok(effective) => format!("OK {}", serde_json::to_string(&effective)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "SET_PARENTS" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.set_parents(parts[1], &parts[2..]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "PARENTS" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.get_parents(parts[1]).await {
+                        Ok(list) => format!("OK {}", serde_json::to_string(&list)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "CREATE_INDEX" | "DROP_INDEX" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let res = if command == "CREATE_INDEX" {
+                        store.create_index(parts[1], parts[2]).await
+                    } else {
+                        store.drop_index(parts[1], parts[2]).await
+                    };
+                    match res {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "LIST_INDEXES" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.list_indexes(parts[1]).await {
+                        Ok(list) => format!("OK {}", serde_json::to_string(&list)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "FIND_INDEX" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let val_str = parts[3..].join(" ");
+                    match serde_json::from_str::<serde_json::Value>(&val_str) {
+                        Ok(val) => match store.find_by_index(parts[1], parts[2], &val).await {
+                            Ok(matches) => {
+                                let out: Vec<serde_json::Value> = matches.into_iter()
+                                    .map(|(persona, key)| serde_json::json!({ "persona": persona, "key": key }))
+                                    .collect();
+                                format!("OK {}", serde_json::to_string(&out)?)
+                            },
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid json value".to_string(),
+                    }
+                }
+            }
+            "ENABLE_SEARCH" | "DISABLE_SEARCH" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let res = if command == "ENABLE_SEARCH" {
+                        store.enable_search(parts[1]).await
+                    } else {
+                        store.disable_search(parts[1]).await
+                    };
+                    match res {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "SEARCH" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    // Terms may be quoted: SEARCH persona app "some terms"
+                    let terms = parts[3..].join(" ");
+                    match store.search(parts[1], parts[2], terms.trim_matches('"')).await {
+                        Ok(hits) => format!("OK {}", serde_json::to_string(&hits)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "HISTORY" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.history(parts[1], parts[2], parts[3]).await {
+                        Ok(entries) => format!("OK {}", serde_json::to_string(&entries)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "GET_AT" | "REVERT" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match parts[4].parse::<u64>() {
+                        Ok(timestamp) if command == "GET_AT" => match store.get_at(parts[1], parts[2], parts[3], timestamp).await {
+                            Ok(val) => format!("OK {}", serde_json::to_string(&val)?),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Ok(timestamp) => match store.revert(parts[1], parts[2], parts[3], timestamp).await {
+                            Ok(_) => "OK".to_string(),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid timestamp".to_string(),
+                    }
+                }
+            }
+            "TRASH" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.list_trash(parts[1]).await {
+                        Ok(entries) => format!("OK {}", serde_json::to_string(&entries)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "RESTORE" | "PURGE" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let res = if command == "RESTORE" {
+                        store.restore(parts[1], parts[2], parts[3]).await
+                    } else {
+                        store.purge(parts[1], parts[2], parts[3]).await
+                    };
+                    match res {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "DEFINE_DERIVED" => {
+                if parts.len() < 5 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let val_str = parts[4..].join(" ");
+                    match serde_json::from_str::<Derivation>(&val_str) {
+                        Ok(derivation) => match store.define_derived(parts[1], parts[2], parts[3], derivation).await {
+                            Ok(_) => "OK".to_string(),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid derivation".to_string(),
+                    }
+                }
+            }
+            "DROP_DERIVED" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.drop_derived(parts[1], parts[2], parts[3]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "LIST_DERIVED" => {
+                match store.list_derived().await {
+                    Ok(defs) => format!("OK {}", serde_json::to_string(&defs)?),
+                    Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                }
+            }
+            "SET_SCHEMA" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    let val_str = parts[2..].join(" ");
+                    match serde_json::from_str(&val_str) {
+                        Ok(schema) => match store.set_schema(parts[1], schema).await {
+                            Ok(_) => "OK".to_string(),
+                            Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                        },
+                        Err(_) => "ERR invalid json value".to_string(),
+                    }
+                }
+            }
+            "GET_SCHEMA" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.get_schema(parts[1]).await {
+                        Ok(schema) => format!("OK {}", serde_json::to_string(&schema)?),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "DEL_SCHEMA" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.remove_schema(parts[1]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "VAULT_SET" => {
+                if parts.len() < 6 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.vault_set(parts[1], parts[2], parts[3], parts[4], parts[5], parts.get(6).copied()).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "FIND_BLIND" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.find_blind(parts[1], parts[2]).await {
+                        Ok(matches) => {
+                            let out: Vec<serde_json::Value> = matches.into_iter()
+                                .map(|(persona, key)| serde_json::json!({ "persona": persona, "key": key }))
+                                .collect();
+                            format!("OK {}", serde_json::to_string(&out)?)
+                        },
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
             "MOVE" => {
                 if parts.len() < 5 {
                     "ERR missing arguments".to_string()
@@ -174,6 +802,56 @@ pub async fn handle_connection(mut socket: TcpStream, store: Arc<dyn CelerixStor
                     }
                 }
             }
+            "MOVE_APP" => {
+                if parts.len() < 4 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.move_app(parts[1], parts[2], parts[3]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "DEL_APP" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.delete_app(parts[1], parts[2]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "DEL_PERSONA" => {
+                if parts.len() < 2 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.delete_persona(parts[1]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "RENAME_PERSONA" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.rename_persona(parts[1], parts[2]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
+            "COPY_PERSONA" => {
+                if parts.len() < 3 {
+                    "ERR missing arguments".to_string()
+                } else {
+                    match store.copy_persona(parts[1], parts[2]).await {
+                        Ok(_) => "OK".to_string(),
+                        Err(e) => format!("ERR {}", e.to_string().to_lowercase()),
+                    }
+                }
+            }
             "PING" => "PONG".to_string(),
             "QUIT" => break,
             _ => "ERR unknown command".to_string(),
@@ -0,0 +1,42 @@
+use std::process::Command;
+use serde_json::Value;
+
+/// Runs the `celerix` CLI against `data_dir` and returns its stdout, failing on a non-zero exit.
+fn celerix(data_dir: &std::path::Path, envs: &[(&str, &str)], args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_celerix"))
+        .arg("--data-dir").arg(data_dir)
+        .args(args)
+        .env_remove("CELERIX_STORE_ADDR")
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "celerix {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_cli_history_from_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let env = [("CELERIX_HISTORY_VERSIONS", "5")];
+    celerix(dir.path(), &env, &["set", "p1", "settings", "theme", "light"]);
+    celerix(dir.path(), &env, &["set", "p1", "settings", "theme", "dark"]);
+
+    let history: Vec<Value> = serde_json::from_str(&celerix(dir.path(), &env, &["history", "p1", "settings", "theme"])).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["value"], "dark");
+    assert_eq!(history[1]["value"], "light");
+}
+
+#[test]
+fn test_cli_trash_from_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let env = [("CELERIX_TRASH_RETENTION_SECS", "3600")];
+    celerix(dir.path(), &env, &["set", "p1", "settings", "theme", "dark"]);
+    celerix(dir.path(), &env, &["del", "p1", "settings", "theme"]);
+
+    let trash: Vec<Value> = serde_json::from_str(&celerix(dir.path(), &env, &["trash", "p1"])).unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0]["key"], "theme");
+    celerix(dir.path(), &env, &["restore", "p1", "settings", "theme"]);
+    assert_eq!(celerix(dir.path(), &env, &["get", "p1", "settings", "theme"]).trim(), "\"dark\"");
+}
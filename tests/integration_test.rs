@@ -4,6 +4,7 @@ use std::sync::Arc;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -12,6 +13,22 @@ struct User {
     age: i32,
 }
 
+/// Serves `store` on an ephemeral local port and returns its address.
+async fn spawn_server(store: Arc<MemStore>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let s = store.clone();
+            tokio::spawn(async move {
+                let _ = celerix_store::server::router::handle_connection(socket, s).await;
+            });
+        }
+    });
+    addr
+}
+
 #[tokio::test]
 async fn test_generic_helpers() {
     let store = Arc::new(MemStore::new(HashMap::new(), None));
@@ -38,6 +55,25 @@ async fn test_generic_helpers() {
     assert_eq!(user, got_user);
 }
 
+#[tokio::test]
+async fn test_get_path_generic() {
+    use celerix_store::{Error, KVReader};
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    let profile = serde_json::json!({"owner": {"name": "Alice", "age": 30}, "tags": ["a", "b"]});
+    client.set_generic("p1", "a1", "profile", &profile).await.unwrap();
+
+    let owner: User = client.get_path_generic("p1", "a1", "profile", "/owner").await.unwrap();
+    assert_eq!(owner, User { name: "Alice".to_string(), age: 30 });
+    let tag: String = client.get_path_generic("p1", "a1", "profile", "/tags/1").await.unwrap();
+    assert_eq!(tag, "b");
+    assert_eq!(client.get_path("p1", "a1", "profile", "").await.unwrap(), profile);
+    assert!(matches!(client.get_path("p1", "a1", "profile", "/missing").await, Err(Error::PathNotFound)));
+}
+
 #[tokio::test]
 async fn test_full_protocol_integration() {
     let store = Arc::new(MemStore::new(HashMap::new(), None));
@@ -80,3 +116,531 @@ async fn test_full_protocol_integration() {
     assert!(response.trim().contains("p1"));
     assert!(response.trim().contains("v1"));
 }
+
+#[tokio::test]
+async fn test_remote_vault_blind_index() {
+    use celerix_store::{CelerixStore, GlobalSearcher, engine::vault};
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+    let master_key = b"thisis32byteslongsecretkey123456";
+
+    let app = client.app("p1", "users");
+    let v = app.vault(master_key);
+    assert!(v.lookup("alice@example.com").await.unwrap().is_empty());
+    v.set_indexed("u1", "alice@example.com").await.unwrap();
+    assert_eq!(v.lookup("alice@example.com").await.unwrap(), vec!["u1".to_string()]);
+
+    let digest = vault::blind_index("alice@example.com", master_key).unwrap();
+    let found = client.find_blind("users", &digest).await.unwrap();
+    assert_eq!(found, vec![("p1".to_string(), "u1".to_string())]);
+
+    assert_eq!(v.list().await.unwrap(), vec!["u1".to_string()]);
+    assert_eq!(v.metadata("u1").await.unwrap().key_id, vault::key_id(master_key).unwrap());
+
+    // Rewriting without an index drops the old digest in the same step
+    v.set("u1", "bob@example.com").await.unwrap();
+    assert!(client.find_blind("users", &digest).await.unwrap().is_empty());
+    assert_eq!(v.get("u1").await.unwrap().expose(), "bob@example.com");
+
+    v.delete("u1").await.unwrap();
+    assert!(!v.exists("u1").await.unwrap());
+    assert!(client.find_blind("users", &digest).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_remote_incr() {
+    use celerix_store::{Error, KVReader, KVWriter};
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    assert_eq!(client.incr("p1", "a1", "hits", 1).await.unwrap(), 1);
+    assert_eq!(client.incr("p1", "a1", "hits", -3).await.unwrap(), -2);
+    assert_eq!(client.get("p1", "a1", "hits").await.unwrap(), serde_json::json!(-2));
+
+    client.set("p1", "a1", "name", serde_json::json!("bob")).await.unwrap();
+    assert!(matches!(client.incr("p1", "a1", "name", 1).await, Err(Error::WrongType(_))));
+    client.set("p1", "a1", "max", serde_json::json!(i64::MAX)).await.unwrap();
+    assert!(matches!(client.incr("p1", "a1", "max", 1).await, Err(Error::OutOfRange(_))));
+}
+
+#[tokio::test]
+async fn test_remote_patch() {
+    use celerix_store::{CelerixStore, Error, Patch};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    let app = client.app("p1", "app1");
+    app.set("key2", json!({"sub": "val"})).await.unwrap();
+    assert_eq!(app.patch("key2", Patch::Merge(json!({"other": 1}))).await.unwrap(), json!({"sub": "val", "other": 1}));
+
+    let res = app.patch("key2", Patch::Json(json!([{"op": "remove", "path": "/missing"}]))).await;
+    assert!(matches!(res, Err(Error::PatchFailed(_))));
+}
+
+#[tokio::test]
+async fn test_remote_lists_and_sets() {
+    use celerix_store::{CelerixStore, ListEnd};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    let app = client.app("p1", "work");
+    assert_eq!(app.list_push("jobs", ListEnd::Back, json!({"id": 1})).await.unwrap(), 1);
+    assert_eq!(app.list_push("jobs", ListEnd::Back, json!({"id": 2})).await.unwrap(), 2);
+    assert_eq!(app.list_len("jobs").await.unwrap(), 2);
+    assert_eq!(app.list_range("jobs", 0, -1).await.unwrap(), vec![json!({"id": 1}), json!({"id": 2})]);
+    assert_eq!(app.list_pop("jobs", ListEnd::Front).await.unwrap(), Some(json!({"id": 1})));
+    assert_eq!(app.list_pop("jobs", ListEnd::Front).await.unwrap(), Some(json!({"id": 2})));
+    assert_eq!(app.list_pop("jobs", ListEnd::Front).await.unwrap(), None);
+
+    assert!(app.set_add("tags", json!("a b")).await.unwrap());
+    assert!(!app.set_add("tags", json!("a b")).await.unwrap());
+    assert!(app.set_contains("tags", &json!("a b")).await.unwrap());
+    assert_eq!(app.set_members("tags").await.unwrap(), vec![json!("a b")]);
+    assert!(app.set_remove("tags", &json!("a b")).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_remote_queue() {
+    use celerix_store::QueueOperations;
+    use serde_json::json;
+    use std::time::Duration;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+    let visibility = Duration::from_secs(30);
+
+    assert!(client.dequeue("p1", "svc", "jobs", visibility, Duration::from_millis(20)).await.unwrap().is_none());
+
+    let id = client.enqueue("p1", "svc", "jobs", json!({"task": "resize"})).await.unwrap();
+    let msg = client.dequeue("p1", "svc", "jobs", visibility, Duration::ZERO).await.unwrap().unwrap();
+    assert_eq!(msg.id, id);
+    assert_eq!(msg.body, json!({"task": "resize"}));
+    client.ack("p1", "svc", "jobs", &msg.receipt).await.unwrap();
+
+    assert!(client.dead_letters("p1", "svc", "jobs").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_remote_locks_and_session_release() {
+    use celerix_store::{Error, LockManager};
+    use std::time::Duration;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let ttl = Duration::from_secs(60);
+
+    let holder = Client::connect(&addr.to_string()).await.unwrap();
+    let other = Client::connect(&addr.to_string()).await.unwrap();
+
+    let lease = holder.acquire_lock("p1", "svc", "leader", ttl).await.unwrap().unwrap();
+    assert!(other.acquire_lock("p1", "svc", "leader", ttl).await.unwrap().is_none());
+    assert!(matches!(other.release_lock("p1", "svc", "leader", lease.token + 1).await, Err(Error::LockNotHeld)));
+    holder.release_lock("p1", "svc", "leader", lease.token).await.unwrap();
+
+    // A session lock is released as soon as its connection goes away
+    let session = holder.acquire_session_lock("p1", "svc", "cron", ttl).await.unwrap().unwrap();
+    assert!(other.acquire_lock("p1", "svc", "cron", ttl).await.unwrap().is_none());
+    drop(holder);
+
+    let mut taken = None;
+    for _ in 0..50 {
+        taken = other.acquire_lock("p1", "svc", "cron", ttl).await.unwrap();
+        if taken.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(taken.unwrap().token > session.token);
+}
+
+#[tokio::test]
+async fn test_remote_mget_mset() {
+    use celerix_store::{KVReader, KVWriter};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store.clone()).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set_many(vec![
+        ("p1", "profile", "name", json!("Ada Lovelace")),
+        ("p1", "profile", "prefs", json!({"theme": "dark"})),
+        ("p2", "profile", "name", json!("Charles Babbage")),
+    ]).await.unwrap();
+
+    let values = client.get_many(&[("p1", "profile", "name"), ("p1", "profile", "prefs"), ("p1", "profile", "missing"), ("p2", "profile", "name")]).await.unwrap();
+    assert_eq!(values, vec![Some(json!("Ada Lovelace")), Some(json!({"theme": "dark"})), None, Some(json!("Charles Babbage"))]);
+    assert_eq!(store.get("p2", "profile", "name").await.unwrap(), json!("Charles Babbage"));
+}
+
+#[tokio::test]
+async fn test_remote_exists_stat_keys() {
+    use celerix_store::{AppEnumeration, Error, KVReader, KVWriter};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set("p1", "profile", "name", json!("Ada")).await.unwrap();
+    client.set("p1", "profile", "email", json!("ada@example.com")).await.unwrap();
+
+    assert!(client.exists("p1", "profile", "name").await.unwrap());
+    assert!(!client.exists("p1", "profile", "phone").await.unwrap());
+    assert_eq!(client.get_keys("p1", "profile").await.unwrap(), vec!["email", "name"]);
+    assert_eq!(client.get_apps("p1").await.unwrap(), vec!["profile"]);
+    assert!(matches!(client.set("p1", "profile::meta", "name", json!({})).await, Err(Error::InvalidAppId(_))));
+    assert_eq!(client.stat("p1", "profile", "name").await.unwrap().version, 1);
+
+    let stat = client.stat("p1", "profile", "name").await.unwrap();
+    assert_eq!(stat.size_bytes, 5);
+    assert_eq!(stat.version, 1);
+    assert!(matches!(client.stat("p1", "profile", "phone").await, Err(Error::KeyNotFound)));
+}
+
+#[tokio::test]
+async fn test_remote_persona_lifecycle() {
+    use celerix_store::{AppEnumeration, Error, KVReader, KVWriter, Orchestrator};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set("p1", "a1", "k", json!(1)).await.unwrap();
+    client.set("p1", "a2", "k", json!(2)).await.unwrap();
+
+    client.rename_persona("p1", "p2").await.unwrap();
+    client.copy_persona("p2", "p3").await.unwrap();
+    assert!(matches!(client.rename_persona("p2", "p3").await, Err(Error::AlreadyExists)));
+
+    client.move_app("p2", "p4", "a1").await.unwrap();
+    assert_eq!(client.get("p4", "a1", "k").await.unwrap(), json!(1));
+    assert!(!client.get_apps("p2").await.unwrap().contains(&"a1".to_string()));
+
+    client.delete_app("p3", "a1").await.unwrap();
+    assert!(matches!(client.get("p3", "a1", "k").await, Err(Error::AppNotFound)));
+    assert!(!client.get_apps("p3").await.unwrap().contains(&"a1::meta".to_string()));
+    client.delete_persona("p3").await.unwrap();
+    assert!(matches!(client.delete_persona("p3").await, Err(Error::PersonaNotFound)));
+}
+
+#[tokio::test]
+async fn test_remote_find_global() {
+    use celerix_store::{GlobalSearcher, KVWriter, SYSTEM_PERSONA};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set(SYSTEM_PERSONA, "flags", "beta", json!(false)).await.unwrap();
+    client.set("p1", "flags", "beta", json!(true)).await.unwrap();
+    client.set("p2", "flags", "beta", json!({"rollout": 10})).await.unwrap();
+
+    let all = client.find_global("flags", "beta", &[]).await.unwrap();
+    assert_eq!(all, vec![
+        ("p1".to_string(), json!(true)),
+        ("p2".to_string(), json!({"rollout": 10})),
+        (SYSTEM_PERSONA.to_string(), json!(false)),
+    ]);
+
+    let preferred = client.find_global("flags", "beta", &["p2"]).await.unwrap();
+    assert_eq!(preferred[0].0, "p2");
+    assert!(client.find_global("flags", "missing", &[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_remote_layered_config() {
+    use celerix_store::{KVWriter, LayeredConfig, SYSTEM_PERSONA};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set(SYSTEM_PERSONA, "billing", "plan", json!({"seats": 5, "support": "email"})).await.unwrap();
+    client.set("org1", "billing", "plan", json!({"seats": 50})).await.unwrap();
+    client.set_parents("team1", &["org1"]).await.unwrap();
+    assert_eq!(client.get_parents("team1").await.unwrap(), vec!["org1"]);
+
+    let plan = client.get_effective("team1", "billing", "plan", true).await.unwrap();
+    assert_eq!(plan.value, json!({"seats": 50, "support": "email"}));
+    assert_eq!(plan.sources["/seats"], "org1");
+    assert_eq!(plan.sources["/support"], SYSTEM_PERSONA);
+
+    client.set_parents("team1", &[]).await.unwrap();
+    assert!(client.get_parents("team1").await.unwrap().is_empty());
+    assert_eq!(client.get_effective("team1", "billing", "plan", false).await.unwrap().value["seats"], json!(5));
+}
+
+#[tokio::test]
+async fn test_remote_query() {
+    use celerix_store::{BatchExporter, KVWriter, Predicate, Query};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set("p1", "orders", "o1", json!({"status": "open", "total": 20})).await.unwrap();
+    client.set("p1", "orders", "o2", json!({"status": "shipped", "total": 99})).await.unwrap();
+    client.set("p2", "orders", "o3", json!({"status": "open", "total": 150})).await.unwrap();
+
+    let open = Query {
+        filter: Some(Predicate::Eq { path: "/status".to_string(), value: json!("open") }),
+        fields: vec!["/total".to_string()],
+    };
+    let found = client.query("p1", "orders", &open).await.unwrap();
+    assert_eq!(found, HashMap::from([("o1".to_string(), json!({"total": 20}))]));
+
+    let big = Query {
+        filter: Some(Predicate::Gt { path: "/total".to_string(), value: json!(50) }),
+        ..Query::default()
+    };
+    let across = client.query_app("orders", &big).await.unwrap();
+    assert_eq!(across["p1"].keys().collect::<Vec<_>>(), vec!["o2"]);
+    assert_eq!(across["p2"]["o3"]["status"], json!("open"));
+}
+
+#[tokio::test]
+async fn test_remote_secondary_index() {
+    use celerix_store::{IndexManager, KVWriter};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.create_index("accounts", "/owner/email").await.unwrap();
+    assert_eq!(client.list_indexes("accounts").await.unwrap(), vec!["/owner/email"]);
+
+    client.set("p1", "accounts", "a1", json!({"owner": {"email": "ann@x.io"}})).await.unwrap();
+    client.set("p2", "accounts", "a2", json!({"owner": {"email": "ann@x.io"}})).await.unwrap();
+
+    let found = client.find_by_index("accounts", "/owner/email", &json!("ann@x.io")).await.unwrap();
+    assert_eq!(found, vec![("p1".to_string(), "a1".to_string()), ("p2".to_string(), "a2".to_string())]);
+
+    client.drop_index("accounts", "/owner/email").await.unwrap();
+    assert!(client.list_indexes("accounts").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_remote_search() {
+    use celerix_store::{KVWriter, TextSearch};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.enable_search("docs").await.unwrap();
+    client.set("p1", "docs", "d1", json!({"title": "Deploying celerix", "tags": ["ops", "docker"]})).await.unwrap();
+    client.set("p1", "docs", "d2", json!({"title": "Docker networking deep dive on docker hosts"})).await.unwrap();
+    client.set("p1", "docs", "d3", json!({"title": "Recipes"})).await.unwrap();
+
+    let hits = client.search("p1", "docs", "Docker \"ops\"").await.unwrap();
+    let keys: Vec<&str> = hits.iter().map(|h| h.key.as_str()).collect();
+    assert_eq!(keys.len(), 2);
+    assert!(hits[0].score >= hits[1].score);
+    assert!(client.search("p1", "docs", "nothing").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_remote_aggregate() {
+    use celerix_store::{Aggregation, BatchExporter, KVWriter};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set("p1", "orders", "o1", json!({"status": "open", "total": 10})).await.unwrap();
+    client.set("p1", "orders", "o2", json!({"status": "paid", "total": 25.5})).await.unwrap();
+    client.set("p2", "orders", "o3", json!({"status": "paid", "total": 4.5})).await.unwrap();
+
+    let totals = Aggregation {
+        group_by: Some("/status".to_string()),
+        sum: vec!["/total".to_string()],
+        ..Default::default()
+    };
+    let groups = client.aggregate("orders", &totals).await.unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!((groups[0].group.clone(), groups[0].count), (Some(json!("open")), 1));
+    assert_eq!((groups[1].count, groups[1].sum["/total"]), (2, 30.0));
+}
+
+#[tokio::test]
+async fn test_remote_schema_validation() {
+    use celerix_store::{Error, KVReader, KVWriter, SchemaRegistry};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    let schema = json!({"type": "object", "required": ["name"], "properties": {"age": {"type": "integer", "minimum": 0}}});
+    client.set_schema("people", schema.clone()).await.unwrap();
+    assert_eq!(client.get_schema("people").await.unwrap(), schema);
+
+    client.set("p1", "people", "ann", json!({"name": "Ann", "age": 31})).await.unwrap();
+    match client.set("p1", "people", "bob", json!({"age": -1})).await {
+        Err(Error::ValidationFailed(errors)) => assert_eq!(errors, vec!["/: missing required property name", "/age: expected at least 0"]),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(matches!(client.get("p1", "people", "bob").await, Err(Error::KeyNotFound)));
+
+    client.remove_schema("people").await.unwrap();
+    client.set("p1", "people", "bob", json!({"age": -1})).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_remote_history() {
+    use celerix_store::engine::history::HistoryRetention;
+    use celerix_store::{Error, KVReader, KVWriter, KeyHistory};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None).with_history(HistoryRetention::Versions(10)));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set("p1", "config", "limits", json!({"max": 10})).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    client.set("p1", "config", "limits", json!({"max": 0})).await.unwrap();
+
+    let history = client.history("p1", "config", "limits").await.unwrap();
+    assert_eq!(history.len(), 2);
+    let first = history[1].at;
+    assert_eq!(client.get_at("p1", "config", "limits", first).await.unwrap(), json!({"max": 10}));
+
+    client.revert("p1", "config", "limits", first).await.unwrap();
+    assert_eq!(client.get("p1", "config", "limits").await.unwrap(), json!({"max": 10}));
+    assert_eq!(client.history("p1", "config", "limits").await.unwrap().len(), 3);
+
+    client.delete("p1", "config", "limits").await.unwrap();
+    let history = client.history("p1", "config", "limits").await.unwrap();
+    assert!(history[0].deleted);
+    assert_eq!(history.len(), 4);
+    assert!(matches!(client.get_at("p1", "config", "limits", history[0].at).await, Err(Error::KeyNotFound)));
+}
+
+#[tokio::test]
+async fn test_remote_trash() {
+    use celerix_store::{KVReader, KVWriter, TrashBin};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None).with_trash(std::time::Duration::from_secs(3600)));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set("p1", "settings", "theme", json!({"mode": "dark"})).await.unwrap();
+    client.delete("p1", "settings", "theme").await.unwrap();
+
+    let trash = client.list_trash("p1").await.unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].key, "theme");
+
+    client.restore("p1", "settings", "theme").await.unwrap();
+    assert_eq!(client.get("p1", "settings", "theme").await.unwrap(), json!({"mode": "dark"}));
+
+    client.delete("p1", "settings", "theme").await.unwrap();
+    client.purge("p1", "settings", "theme").await.unwrap();
+    assert!(client.list_trash("p1").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_webhook_hook() {
+    use celerix_store::engine::hooks::{ChangeEvent, ChangeKind, HookScope};
+    use celerix_store::server::hooks;
+    use celerix_store::KVWriter;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/events", listener.local_addr().unwrap());
+
+    let store = MemStore::new(HashMap::new(), None);
+    let hook = hooks::webhook(&url).unwrap();
+    store.on_after_set(HookScope::default().app("settings"), move |e| hook(e));
+    store.set("p1", "other", "k", json!(0)).await.unwrap();
+    store.set("p1", "settings", "theme", json!("dark")).await.unwrap();
+
+    let (mut socket, _) = listener.accept().await.unwrap();
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !String::from_utf8_lossy(&request).contains("}") {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+    }
+    socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+
+    let request = String::from_utf8(request).unwrap();
+    assert!(request.starts_with("POST /events HTTP/1.1\r\n"));
+    let body = request.split("\r\n\r\n").nth(1).unwrap();
+    let event: ChangeEvent = serde_json::from_str(body).unwrap();
+    assert_eq!((event.kind, event.key.as_str(), event.value), (ChangeKind::Set, "theme", Some(json!("dark"))));
+}
+
+#[tokio::test]
+async fn test_remote_derived_keys() {
+    use celerix_store::{Derivation, DerivedKeys, Error, KVReader, KVWriter, KeyRef};
+    use serde_json::json;
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+
+    client.set("p1", "base", "cfg", json!({"a": 1, "b": 1})).await.unwrap();
+    let sources = vec![KeyRef::new("p1", "base", "cfg"), KeyRef::new("p1", "local", "cfg")];
+    client.define_derived("p1", "merged", "cfg", Derivation::Merge { sources }).await.unwrap();
+    client.set("p1", "local", "cfg", json!({"b": 2})).await.unwrap();
+
+    assert_eq!(client.get("p1", "merged", "cfg").await.unwrap(), json!({"a": 1, "b": 2}));
+    let defs = client.list_derived().await.unwrap();
+    assert_eq!(defs[0].target, KeyRef::new("p1", "merged", "cfg"));
+    assert!(matches!(client.set("p1", "merged", "cfg", json!({})).await, Err(Error::ReadOnly(_))));
+    let sources = vec![KeyRef::new("p1", "base", "cfg")];
+    assert!(matches!(client.define_derived("p1", "local", "cfg", Derivation::Merge { sources }).await, Err(Error::AlreadyExists)));
+
+    client.drop_derived("p1", "merged", "cfg").await.unwrap();
+    assert!(client.list_derived().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_remote_vault_grants() {
+    use celerix_store::{CelerixStore, Error};
+
+    let store = Arc::new(MemStore::new(HashMap::new(), None));
+    let addr = spawn_server(store).await;
+    let client = Client::connect(&addr.to_string()).await.unwrap();
+    let key_a = b"thisis32byteslongsecretkey123456";
+    let key_b = b"another32byteslongsecretkey65432";
+
+    let app_a = client.app("alice", "shared");
+    let vault_a = app_a.vault(key_a);
+    let app_b = client.app("bob", "shared");
+    let vault_b = app_b.vault(key_b);
+
+    let recipient_key = vault_b.recipient_key("alice").unwrap();
+    vault_a.set("api_token", "tok-123").await.unwrap();
+    vault_a.grant("api_token", "bob", recipient_key.expose()).await.unwrap();
+    assert_eq!(vault_b.get_grant("alice", "api_token").await.unwrap().expose(), "tok-123");
+
+    vault_a.set("api_token", "tok-456").await.unwrap();
+    assert_eq!(vault_b.get_grant("alice", "api_token").await.unwrap().expose(), "tok-456");
+
+    vault_a.revoke("api_token", "bob").await.unwrap();
+    assert!(matches!(vault_b.get_grant("alice", "api_token").await, Err(Error::KeyNotFound)));
+}